@@ -0,0 +1,266 @@
+// @Brief : This file contains the state of the batteries, and the AC
+//          adapter, from `/sys/class/power_supply`.
+
+use crate::{FetchError, Options};
+use std::fmt;
+use std::time::Duration;
+
+/// The batteries of the machine, and whether it is plugged in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatteryInfo {
+    /// The batteries powering the machine, e.g. both "BAT0" and "BAT1" of
+    /// some ThinkPads, leaving out the ones of a wireless mouse.
+    pub batteries: Vec<Battery>,
+    /// Whether an AC adapter, or USB charger, is connected, or `None`, if
+    /// the machine does not report any.
+    pub ac_online: Option<bool>,
+}
+
+/// A single battery, e.g. "BAT0".
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Battery {
+    /// Name of the power supply, e.g. "BAT0".
+    pub name: String,
+    /// Charge left, in percent.
+    pub capacity: Option<u8>,
+    /// e.g. "Charging", "Discharging", "Full", or "Not charging".
+    pub status: Option<String>,
+    /// Time until the battery is empty while discharging, or full while
+    /// charging.
+    pub time_remaining: Option<Duration>,
+    /// Number of charge cycles, the battery went through.
+    pub cycle_count: Option<u32>,
+    /// Capacity, when fully charged, relative to the design capacity, in
+    /// percent, e.g. 92 for a worn out battery.
+    pub health: Option<u8>,
+}
+
+impl fmt::Display for Battery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut details = Vec::new();
+        details.extend(self.status.clone());
+        if let Some(remaining) = self.time_remaining {
+            let remaining = crate::format_uptime(remaining).replace(", ", " ");
+            match self.status.as_deref() {
+                Some("Charging") => details.push(format!("{} until full", remaining)),
+                _ => details.push(format!("{} left", remaining)),
+            }
+        }
+        if let Some(health) = self.health {
+            details.push(format!("{}% health", health));
+        }
+        if let Some(cycles) = self.cycle_count {
+            details.push(format!("{} cycles", cycles));
+        }
+
+        // Without the capacity, the details are all there is to display.
+        match (self.capacity, details.is_empty()) {
+            (Some(capacity), true) => write!(f, "{}%", capacity),
+            (Some(capacity), false) => write!(f, "{}% ({})", capacity, details.join(", ")),
+            (None, _) => f.write_str(&details.join(", ")),
+        }
+    }
+}
+
+impl fmt::Display for BatteryInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Only name the batteries, when there is more than one of them.
+        // A single battery, that reports nothing at all, is left out.
+        let mut parts: Vec<String> = match self.batteries.as_slice() {
+            [battery] => vec![battery.to_string()],
+            batteries => batteries
+                .iter()
+                .map(|battery| {
+                    format!("{} {}", battery.name, battery)
+                        .trim_end()
+                        .to_string()
+                })
+                .collect(),
+        };
+        parts.retain(|part| !part.is_empty());
+        match self.ac_online {
+            Some(true) => parts.push("AC connected".to_string()),
+            Some(false) => parts.push("AC disconnected".to_string()),
+            None => {}
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Read the batteries, and the AC adapters, from `/sys/class/power_supply`.
+pub fn read(opts: &Options) -> Result<BatteryInfo, FetchError> {
+    let supplies = opts.attempt("/sys/class/power_supply", || {
+        opts.root().read_dir("/sys/class/power_supply")
+    })?;
+
+    let mut info = BatteryInfo::default();
+    for name in supplies {
+        let dir = format!("/sys/class/power_supply/{}", name);
+        let attr = |file: &str| {
+            crate::read_traced(opts, &format!("{}/{}", dir, file))
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let number = |file: &str| attr(file).and_then(|value| value.parse::<u64>().ok());
+
+        match attr("type").as_deref() {
+            // Peripherals, e.g. a mouse, or a headset, have the scope
+            // "Device", unlike the batteries of the machine itself.
+            Some("Battery") if attr("scope").as_deref() != Some("Device") => {}
+            Some("Mains") | Some("USB") => {
+                if let Some(online) = number("online") {
+                    info.ac_online = Some(info.ac_online == Some(true) || online == 1);
+                }
+                continue;
+            }
+            _ => continue,
+        }
+
+        // Batteries report either their energy, in µWh and µW, or their
+        // charge, in µAh and µA, which works out the same for the ratios.
+        let (now, full, design, rate) = match number("energy_full") {
+            Some(full) => (
+                number("energy_now"),
+                Some(full),
+                number("energy_full_design"),
+                number("power_now"),
+            ),
+            None => (
+                number("charge_now"),
+                number("charge_full"),
+                number("charge_full_design"),
+                number("current_now"),
+            ),
+        };
+
+        let status = attr("status");
+        let time_remaining = match status.as_deref() {
+            Some("Discharging") => number("time_to_empty_now")
+                .map(Duration::from_secs)
+                .or_else(|| hours(now?, rate?)),
+            Some("Charging") => number("time_to_full_now")
+                .map(Duration::from_secs)
+                .or_else(|| hours(full?.saturating_sub(now?), rate?)),
+            _ => None,
+        };
+        let health = match (full, design) {
+            (Some(full), Some(design)) if design > 0 => Some((full * 100 / design).min(100) as u8),
+            _ => None,
+        };
+
+        info.batteries.push(Battery {
+            capacity: number("capacity").map(|capacity| capacity.min(100) as u8),
+            status,
+            time_remaining,
+            // Batteries, that do not count their cycles, report 0.
+            cycle_count: number("cycle_count")
+                .filter(|&cycles| cycles > 0)
+                .map(|cycles| cycles as u32),
+            health,
+            name,
+        });
+    }
+
+    if info.batteries.is_empty() {
+        return Err(FetchError::NotFound("no battery".to_string()));
+    }
+    Ok(info)
+}
+
+/// The time it takes to drain, or fill, `amount` at `rate` per hour.
+fn hours(amount: u64, rate: u64) -> Option<Duration> {
+    (rate > 0).then(|| Duration::from_secs(amount * 3600 / rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    fn supply(fixture: Fixture, name: &str, attrs: &[(&str, &str)]) -> Fixture {
+        attrs.iter().fold(fixture, |fixture, (file, value)| {
+            fixture.file(
+                &format!("/sys/class/power_supply/{}/{}", name, file),
+                &format!("{}\n", value),
+            )
+        })
+    }
+
+    fn detect(fixture: &Fixture) -> Result<BatteryInfo, FetchError> {
+        read(&Options::default().with_root(fixture.root()))
+    }
+
+    #[test]
+    fn energy_and_charge() {
+        let fixture = supply(Fixture::new(), "AC", &[("type", "Mains"), ("online", "0")]);
+        let fixture = supply(
+            fixture,
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Discharging"),
+                ("capacity", "50"),
+                ("energy_now", "28000000"),
+                ("energy_full", "56000000"),
+                ("energy_full_design", "60000000"),
+                ("power_now", "8000000"),
+                ("cycle_count", "312"),
+            ],
+        );
+        let laptop = supply(
+            fixture,
+            "hidpp_battery_0",
+            &[("type", "Battery"), ("scope", "Device"), ("capacity", "5")],
+        );
+        let info = detect(&laptop).unwrap();
+        assert_eq!(info.batteries.len(), 1);
+        assert_eq!(
+            info.batteries[0].time_remaining,
+            Some(Duration::from_secs(3 * 3600 + 30 * 60))
+        );
+        assert_eq!(
+            info.to_string(),
+            "50% (Discharging, 3 h 30 m left, 93% health, 312 cycles), AC disconnected"
+        );
+
+        // Two batteries, reporting their charge, one of them charging.
+        let fixture = supply(
+            Fixture::new(),
+            "BAT0",
+            &[
+                ("type", "Battery"),
+                ("status", "Charging"),
+                ("capacity", "80"),
+                ("charge_now", "4000000"),
+                ("charge_full", "5000000"),
+                ("current_now", "2000000"),
+                ("cycle_count", "0"),
+            ],
+        );
+        let thinkpad = supply(
+            fixture,
+            "BAT1",
+            &[("type", "Battery"), ("status", "Full"), ("capacity", "100")],
+        );
+        assert_eq!(
+            detect(&thinkpad).unwrap().to_string(),
+            "BAT0 80% (Charging, 30 m until full), BAT1 100% (Full)"
+        );
+
+        let no_capacity = supply(
+            Fixture::new(),
+            "BAT0",
+            &[("type", "Battery"), ("status", "Not charging")],
+        );
+        assert_eq!(detect(&no_capacity).unwrap().to_string(), "Not charging");
+
+        // Some ACPI firmware reports neither the capacity, nor the status.
+        let silent = supply(Fixture::new(), "AC", &[("type", "Mains"), ("online", "1")]);
+        let silent = supply(silent, "BAT0", &[("type", "Battery")]);
+        assert_eq!(detect(&silent).unwrap().to_string(), "AC connected");
+
+        let desktop = supply(Fixture::new(), "AC", &[("type", "Mains"), ("online", "1")]);
+        assert!(detect(&desktop).unwrap_err().is_not_found());
+    }
+}
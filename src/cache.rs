@@ -0,0 +1,198 @@
+// @Brief : This file contains the on-disk cache, which keeps the values
+//          of slow modules, e.g. the packages count, between runs.
+
+use crate::SysRoot;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long the value of a module stays valid in the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// The value is collected again, once it is older than this.
+    pub ttl: Duration,
+    /// Absolute system paths, e.g. "/var/lib/pacman/local", whose
+    /// modification time changes along with the value. The cached value
+    /// is discarded as soon as any of them changes, appears or disappears.
+    /// A path ending in `/*`, e.g. "/var/db/pkg/*", stands for every entry
+    /// directly inside the directory.
+    pub watch: Vec<PathBuf>,
+}
+
+/// A directory holding one file for every cached module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Cache { dir: dir.into() }
+    }
+
+    /// The default cache, in `$XDG_CACHE_HOME/fetchit`, or in
+    /// `~/.cache/fetchit` if `XDG_CACHE_HOME` is not set.
+    pub fn default_dir() -> Option<Self> {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".cache")))?;
+        Some(Cache::new(cache_home.join("fetchit")))
+    }
+
+    /// The cached value for the module `name`, if it is still valid
+    /// under `policy` for the system under `root`.
+    pub fn get(&self, name: &str, policy: &CachePolicy, root: &SysRoot) -> Option<String> {
+        let contents = fs::read_to_string(self.dir.join(name)).ok()?;
+        let mut lines = contents.splitn(4, '\n');
+
+        let written: u64 = lines.next()?.parse().ok()?;
+        let cached_root = lines.next()?;
+        let stamp = lines.next()?;
+        let value = lines.next()?;
+
+        let age = now_secs().checked_sub(written)?;
+        let valid = age < policy.ttl.as_secs()
+            && cached_root == root.path("/").to_string_lossy()
+            && stamp == fingerprint(policy, root);
+        valid.then(|| value.to_string())
+    }
+
+    /// Store `value` for the module `name`.
+    pub fn put(
+        &self,
+        name: &str,
+        policy: &CachePolicy,
+        root: &SysRoot,
+        value: &str,
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        // Write to a temporary file first, and rename it into place, so
+        // that a concurrent run never reads a half written value.
+        let contents = format!(
+            "{}\n{}\n{}\n{}",
+            now_secs(),
+            root.path("/").to_string_lossy(),
+            fingerprint(policy, root),
+            value
+        );
+        let tmp = self.dir.join(format!(".{}.{}", name, std::process::id()));
+        fs::write(&tmp, contents)?;
+        fs::rename(tmp, self.dir.join(name))
+    }
+}
+
+/// The modification times of every watched path, in a single line,
+/// with `-` for a path that does not exist.
+fn fingerprint(policy: &CachePolicy, root: &SysRoot) -> String {
+    let mtime = |path: &Path| {
+        fs::metadata(root.path(path))
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map_or_else(|| "-".to_string(), |mtime| mtime.as_nanos().to_string())
+    };
+    policy
+        .watch
+        .iter()
+        .map(|path| match glob_dir(path) {
+            // Each entry by name, so that one appearing, or disappearing,
+            // changes the line as well, e.g. a new category in Gentoo.
+            Some(dir) => root
+                .read_dir(dir)
+                .map(|names| {
+                    names
+                        .iter()
+                        .map(|name| format!("{}={}", name, mtime(&dir.join(name))))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .unwrap_or_else(|_| "-".to_string()),
+            None => mtime(path),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The directory of a watched path ending in `/*`.
+fn glob_dir(path: &Path) -> Option<&Path> {
+    (path.file_name()? == "*").then(|| path.parent()).flatten()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+    use std::fs::File;
+
+    #[test]
+    fn invalidated_by_watched_path() {
+        let fixture = Fixture::new().file("/var/lib/dpkg/status", "Package: bash\n");
+        let cache = Cache::new(fixture.root().path("/cache"));
+        let policy = CachePolicy {
+            ttl: Duration::from_secs(60),
+            watch: vec![PathBuf::from("/var/lib/dpkg/status")],
+        };
+        let root = fixture.root();
+
+        assert_eq!(cache.get("packages", &policy, &root), None);
+        cache.put("packages", &policy, &root, "1").unwrap();
+        assert_eq!(cache.get("packages", &policy, &root).as_deref(), Some("1"));
+
+        // A different root, or an expired value, is not served.
+        assert_eq!(cache.get("packages", &policy, &SysRoot::host()), None);
+        let expired = CachePolicy {
+            ttl: Duration::ZERO,
+            ..policy.clone()
+        };
+        assert_eq!(cache.get("packages", &expired, &root), None);
+
+        // Installing a package touches the watched file.
+        File::options()
+            .append(true)
+            .open(root.path("/var/lib/dpkg/status"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(cache.get("packages", &policy, &root), None);
+    }
+
+    #[test]
+    fn invalidated_by_watched_entries() {
+        let fixture = Fixture::new()
+            .dir("/var/db/pkg/app-shells/bash-5.2_p26")
+            .dir("/var/db/pkg/sys-apps/coreutils-9.4");
+        let cache = Cache::new(fixture.root().path("/cache"));
+        let policy = CachePolicy {
+            ttl: Duration::from_secs(60),
+            watch: vec![PathBuf::from("/var/db/pkg/*")],
+        };
+        let root = fixture.root();
+
+        cache.put("packages", &policy, &root, "2").unwrap();
+        assert_eq!(cache.get("packages", &policy, &root).as_deref(), Some("2"));
+
+        // A new package in an existing category only touches the category,
+        // not `/var/db/pkg` itself.
+        fs::create_dir(root.path("/var/db/pkg/app-shells/zsh-5.9")).unwrap();
+        File::open(root.path("/var/db/pkg/app-shells"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(cache.get("packages", &policy, &root), None);
+
+        // So does a new category.
+        cache.put("packages", &policy, &root, "3").unwrap();
+        fs::create_dir(root.path("/var/db/pkg/dev-vcs")).unwrap();
+        assert_eq!(cache.get("packages", &policy, &root), None);
+    }
+}
@@ -0,0 +1,269 @@
+// @Brief : This file contains the `CommandRunner` trait, through which
+//          every external command is executed, so that the probes can
+//          be tested against canned command output.
+
+use crate::FetchError;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What an external command printed, and how it exited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Exit code of the command, `None` if it was killed by a signal.
+    pub status: Option<i32>,
+    /// Everything the command wrote to stdout.
+    pub stdout: String,
+}
+
+impl CommandOutput {
+    /// Whether the command exited with a status of `0`.
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+
+/// Executes external commands on behalf of the probes.
+pub trait CommandRunner: Send + Sync {
+    /// Run `program` with `args`, and wait for it to finish.
+    ///
+    /// Returns an error only if the command could not be executed at all,
+    /// e.g. because `program` is not installed, or if it did not finish in
+    /// time, in which case the error is of the kind `TimedOut`.
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput>;
+
+    /// Run `program` with `args`, and return its stdout, treating a
+    /// non-zero exit status as an error.
+    fn stdout(&self, program: &str, args: &[&str]) -> Result<String, FetchError> {
+        let command_line = format!("`{}`", command_line(program, args));
+        let output = self
+            .run(program, args)
+            .map_err(|err| FetchError::from_io(&command_line, err))?;
+
+        if output.success() {
+            Ok(output.stdout)
+        } else {
+            Err(FetchError::CommandFailed(format!(
+                "{} exited with status {:?}",
+                command_line, output.status
+            )))
+        }
+    }
+}
+
+/// Runs commands on the real system, using `std::process::Command`.
+///
+/// Every command is supervised, i.e. it runs in its own process group,
+/// which is killed once the command takes longer than the timeout, so
+/// that a hung command can not hang `fetchit` as well.
+///
+/// Clones of a runner share the commands they are running, so any of
+/// them can [`kill_all`](SystemRunner::kill_all) of those.
+#[derive(Debug, Clone)]
+pub struct SystemRunner {
+    timeout: Duration,
+    running: Arc<Mutex<Vec<libc::pid_t>>>,
+}
+
+impl SystemRunner {
+    /// Kill every command, that takes longer than `timeout`.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        SystemRunner {
+            timeout,
+            running: Arc::default(),
+        }
+    }
+
+    /// Kill every command, that is still running, e.g. before exiting,
+    /// once the probes that started them are no longer waited for.
+    pub fn kill_all(&self) {
+        for &pgid in self.lock_running().iter() {
+            kill_group(pgid);
+        }
+    }
+}
+
+/// Kill the process group `pgid`, i.e. a command, and everything it spawned.
+fn kill_group(pgid: libc::pid_t) {
+    // SAFETY: `kill` has no memory safety requirements, and a negative
+    // pid addresses the whole process group.
+    unsafe {
+        libc::kill(-pgid, libc::SIGKILL);
+    }
+}
+
+impl SystemRunner {
+    fn lock_running(&self) -> std::sync::MutexGuard<'_, Vec<libc::pid_t>> {
+        self.running.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn timed_out(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("killed after {:?}", self.timeout),
+        )
+    }
+}
+
+impl Default for SystemRunner {
+    fn default() -> Self {
+        SystemRunner::with_timeout(Duration::from_secs(10))
+    }
+}
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        // Start the command as the leader of a new process group, so that
+        // it can be killed along with anything it spawned itself.
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()?;
+        let pgid = child.id() as libc::pid_t;
+        self.lock_running().push(pgid);
+
+        // Read the output in a separate thread, so that a command, which
+        // fills up the pipe, does not block while we wait for it to exit.
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = tx.send(stdout.read_to_end(&mut buf).map(|_| buf));
+        });
+
+        // Poll the command, backing off up to 20ms between checks, until
+        // it exits, or the deadline passes.
+        let deadline = Instant::now() + self.timeout;
+        let mut interval = Duration::from_millis(1);
+        let status = loop {
+            // The lock is held while the child is reaped, and until it is
+            // no longer listed as running, so `kill_all` never hits a pid,
+            // that has been reused in the meantime.
+            let mut running = self.lock_running();
+            let status = match child.try_wait() {
+                Ok(None) if Instant::now() < deadline => None,
+                Ok(None) => {
+                    kill_group(pgid);
+                    // Reap the child, so it does not linger as a zombie.
+                    let _ = child.wait();
+                    Some(Err(self.timed_out()))
+                }
+                result => Some(result.map(|status| status.expect("child has exited"))),
+            };
+            if let Some(status) = status {
+                running.retain(|&pid| pid != pgid);
+                break status?;
+            }
+            drop(running);
+
+            thread::sleep(interval);
+            interval = (interval * 2).min(Duration::from_millis(20));
+        };
+
+        // Something the command left running in the background may still
+        // hold the pipe open, so the output is only waited for until the
+        // deadline as well.
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let stdout = rx.recv_timeout(remaining).map_err(|_| {
+            kill_group(pgid);
+            self.timed_out()
+        })??;
+        Ok(CommandOutput {
+            status: status.code(),
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        })
+    }
+}
+
+/// Answers commands with canned responses, instead of executing them.
+///
+/// Commands without a response behave as if the program was not installed.
+#[derive(Debug, Clone, Default)]
+pub struct CannedRunner {
+    responses: HashMap<String, CommandOutput>,
+}
+
+impl CannedRunner {
+    pub fn new() -> Self {
+        CannedRunner::default()
+    }
+
+    /// Respond to `command_line`, e.g. "uname -r", with the given
+    /// exit status and stdout.
+    pub fn with(mut self, command_line: &str, status: i32, stdout: &str) -> Self {
+        self.responses.insert(
+            command_line.to_string(),
+            CommandOutput {
+                status: Some(status),
+                stdout: stdout.to_string(),
+            },
+        );
+        self
+    }
+}
+
+impl CommandRunner for CannedRunner {
+    fn run(&self, program: &str, args: &[&str]) -> io::Result<CommandOutput> {
+        let command_line = command_line(program, args);
+
+        self.responses.get(&command_line).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no canned response for `{}`", command_line),
+            )
+        })
+    }
+}
+
+/// The command line for `program` and `args`, e.g. "uname -r".
+pub(crate) fn command_line(program: &str, args: &[&str]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_command() {
+        let output = SystemRunner::default()
+            .run("sh", &["-c", "echo fetchit; exit 3"])
+            .unwrap();
+        assert_eq!(output.status, Some(3));
+        assert_eq!(output.stdout, "fetchit\n");
+    }
+
+    #[test]
+    fn hung_command_is_killed() {
+        // The shell's child keeps the pipe open, so this only finishes in
+        // time if the whole process group is killed.
+        let runner = SystemRunner::with_timeout(Duration::from_millis(100));
+        let start = Instant::now();
+        let err = runner.stdout("sh", &["-c", "sleep 30 & wait"]).unwrap_err();
+
+        assert!(matches!(err, FetchError::Timeout(_)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn background_child_is_killed() {
+        // The shell exits right away, but what it left running in the
+        // background still holds the pipe open.
+        let runner = SystemRunner::with_timeout(Duration::from_millis(100));
+        let start = Instant::now();
+        let err = runner.run("sh", &["-c", "sleep 30 &"]).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,402 @@
+// @Brief : This file contains the CPU info, i.e. its model, topology and
+//          maximum frequency, from `/proc/cpuinfo` and sysfs.
+
+use crate::{FetchError, Options};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// The CPU of the machine, e.g. "AMD Ryzen 7 5800X 8-Core (8C/16T) @
+/// 4.85 GHz".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuInfo {
+    /// Name of the CPU model, e.g. "Intel Core i7-8565U", or "ARM
+    /// Cortex-A76 + Cortex-A55" for a big.LITTLE design.
+    pub model: String,
+    /// Number of physical cores, if the topology is known.
+    pub cores: Option<u32>,
+    /// Number of logical CPUs, i.e. hardware threads.
+    pub threads: u32,
+    /// Number of physical packages, if the topology is known.
+    pub sockets: Option<u32>,
+    /// Highest frequency any of the cores reaches, in MHz.
+    pub max_freq_mhz: Option<u32>,
+}
+
+impl fmt::Display for CpuInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(sockets @ 2..) = self.sockets {
+            write!(f, "{}x ", sockets)?;
+        }
+        write!(f, "{}", self.model)?;
+        match self.cores {
+            Some(cores) if cores != self.threads => write!(f, " ({}C/{}T)", cores, self.threads)?,
+            _ => write!(f, " ({})", self.threads)?,
+        }
+        if let Some(mhz) = self.max_freq_mhz {
+            write!(f, " @ {:.2} GHz", f64::from(mhz) / 1000.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the CPU info, of the system under the root from `opts`.
+pub fn read(opts: &Options) -> Result<CpuInfo, FetchError> {
+    let cpuinfo = crate::read_traced(opts, "/proc/cpuinfo")?;
+    let blocks = parse_blocks(&cpuinfo);
+    let processors: Vec<_> = blocks
+        .iter()
+        .filter(|block| block.contains_key("processor"))
+        .collect();
+    let model = model_name(&blocks)
+        .ok_or_else(|| FetchError::ParseError("/proc/cpuinfo has no CPU model".to_string()))?;
+
+    // The topology in sysfs covers every architecture, while only x86
+    // lists the physical and core IDs in `/proc/cpuinfo`.
+    let topology = opts
+        .attempt("/sys/devices/system/cpu", || sysfs_topology(opts))
+        .ok()
+        .or_else(|| cpuinfo_topology(&processors));
+    let threads = match topology {
+        Some((threads, _, _)) => threads,
+        None => processors.len() as u32,
+    }
+    .max(1);
+
+    Ok(CpuInfo {
+        model,
+        cores: topology.map(|(_, cores, _)| cores),
+        threads,
+        sockets: topology.map(|(_, _, sockets)| sockets),
+        max_freq_mhz: max_freq_mhz(opts),
+    })
+}
+
+/// Split `/proc/cpuinfo` into its blocks, one for each processor, and on
+/// some architectures, a final one for the whole machine.
+fn parse_blocks(cpuinfo: &str) -> Vec<HashMap<&str, &str>> {
+    cpuinfo
+        .split("\n\n")
+        .map(|block| {
+            block
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .collect::<HashMap<_, _>>()
+        })
+        .filter(|block| !block.is_empty())
+        .collect()
+}
+
+/// The name of the CPU model, from the first key, that names it, for
+/// each architecture.
+fn model_name(blocks: &[HashMap<&str, &str>]) -> Option<String> {
+    let find = |key: &str| {
+        blocks
+            .iter()
+            .find_map(|block| block.get(key).copied())
+            .filter(|value| !value.is_empty())
+    };
+
+    // x86, and some ARM boards, e.g. "Intel(R) Core(TM) i7-8565U CPU @ 1.80GHz".
+    if let Some(name) = find("model name").filter(|name| !name.starts_with("ARMv")) {
+        return Some(clean_model(name));
+    }
+
+    // ARM, where each core only lists the IDs of its implementer and part.
+    let parts: Vec<String> = blocks
+        .iter()
+        .filter_map(|block| Some((block.get("CPU implementer")?, block.get("CPU part")?)))
+        .map(|(implementer, part)| arm_core_name(implementer, part))
+        .collect();
+    if !parts.is_empty() {
+        // Keep the order of the cores, e.g. the big ones after the little
+        // ones, but list each kind only once, and the vendor only once.
+        let mut kinds: Vec<&str> = Vec::new();
+        for part in &parts {
+            if !kinds.contains(&part.as_str()) {
+                kinds.push(part);
+            }
+        }
+        let vendor = kinds[0].split(' ').next().unwrap_or_default();
+        let others = kinds[1..]
+            .iter()
+            .map(|kind| kind.strip_prefix(vendor).map_or(*kind, str::trim_start));
+        return Some(
+            std::iter::once(kinds[0])
+                .chain(others)
+                .collect::<Vec<_>>()
+                .join(" + "),
+        );
+    }
+
+    // RISC-V, e.g. "sifive,u74-mc", or only the ISA.
+    if let Some(uarch) = find("uarch") {
+        return Some(uarch.replace(',', " "));
+    }
+    if let Some(isa) = find("isa") {
+        return Some(format!("RISC-V ({})", isa));
+    }
+
+    // PowerPC, MIPS, and old ARM kernels.
+    ["cpu", "cpu model", "Processor", "model name"]
+        .iter()
+        .find_map(|key| find(key))
+        .map(clean_model)
+}
+
+/// Remove the noise from a model name, e.g. "Intel(R) Core(TM) i7-8565U
+/// CPU @ 1.80GHz" becomes "Intel Core i7-8565U".
+fn clean_model(name: &str) -> String {
+    let name = name.split(" @ ").next().unwrap_or(name);
+    name.replace("(R)", "")
+        .replace("(TM)", "")
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "CPU" | "Processor"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The name of an ARM core, from the hexadecimal IDs of its implementer,
+/// and its part number, e.g. "ARM Cortex-A72" for 0x41 and 0xd08.
+fn arm_core_name(implementer: &str, part: &str) -> String {
+    let parse = |id: &str| u32::from_str_radix(id.trim_start_matches("0x"), 16).ok();
+    let (implementer_id, part_id) = (parse(implementer), parse(part));
+
+    let vendor = match implementer_id {
+        Some(0x41) => "ARM",
+        Some(0x42) => "Broadcom",
+        Some(0x43) => "Cavium",
+        Some(0x46) => "Fujitsu",
+        Some(0x48) => "HiSilicon",
+        Some(0x4e) => "NVIDIA",
+        Some(0x50) => "APM",
+        Some(0x51) => "Qualcomm",
+        Some(0x53) => "Samsung",
+        Some(0x56) => "Marvell",
+        Some(0x61) => "Apple",
+        Some(0x69) => "Intel",
+        Some(0xc0) => "Ampere",
+        _ => return format!("ARM {}/{}", implementer, part),
+    };
+
+    let core = match (implementer_id, part_id) {
+        (Some(0x41), Some(part)) => match part {
+            0xc07 => "Cortex-A7",
+            0xc08 => "Cortex-A8",
+            0xc09 => "Cortex-A9",
+            0xc0d | 0xc0e => "Cortex-A17",
+            0xc0f => "Cortex-A15",
+            0xd01 => "Cortex-A32",
+            0xd02 => "Cortex-A34",
+            0xd03 => "Cortex-A53",
+            0xd04 => "Cortex-A35",
+            0xd05 => "Cortex-A55",
+            0xd06 => "Cortex-A65",
+            0xd07 => "Cortex-A57",
+            0xd08 => "Cortex-A72",
+            0xd09 => "Cortex-A73",
+            0xd0a => "Cortex-A75",
+            0xd0b => "Cortex-A76",
+            0xd0c => "Neoverse-N1",
+            0xd0d => "Cortex-A77",
+            0xd40 => "Neoverse-V1",
+            0xd41 => "Cortex-A78",
+            0xd44 => "Cortex-X1",
+            0xd46 => "Cortex-A510",
+            0xd47 => "Cortex-A710",
+            0xd48 => "Cortex-X2",
+            0xd49 => "Neoverse-N2",
+            0xd4b => "Cortex-A78C",
+            0xd4d => "Cortex-A715",
+            0xd4e => "Cortex-X3",
+            0xd4f => "Neoverse-V2",
+            0xd80 => "Cortex-A520",
+            0xd81 => "Cortex-A720",
+            0xd82 => "Cortex-X4",
+            _ => "",
+        },
+        (Some(0x43), Some(0x0af)) => "ThunderX2",
+        (Some(0x48), Some(0xd01)) => "TaiShan v110",
+        (Some(0x4e), Some(0x004)) => "Carmel",
+        (Some(0x51), Some(part)) => match part {
+            0x001 => "Oryon",
+            0x800 | 0x802 | 0x804 => "Kryo Gold",
+            0x801 | 0x803 | 0x805 => "Kryo Silver",
+            0xc00 => "Falkor",
+            _ => "",
+        },
+        (Some(0x61), Some(part)) => match part {
+            0x022 | 0x024 | 0x028 => "Icestorm",
+            0x023 | 0x025 | 0x029 => "Firestorm",
+            0x032 => "Blizzard",
+            0x033 => "Avalanche",
+            _ => "",
+        },
+        (Some(0xc0), Some(0xac3)) => "Ampere-1",
+        (Some(0xc0), Some(0xac4)) => "Ampere-1a",
+        _ => "",
+    };
+
+    if core.is_empty() {
+        format!("{} {}", vendor, part)
+    } else {
+        format!("{} {}", vendor, core)
+    }
+}
+
+/// The number of threads, cores and sockets, from the topology of every
+/// online CPU in sysfs.
+fn sysfs_topology(opts: &Options) -> Result<(u32, u32, u32), FetchError> {
+    let root = opts.root();
+    let mut threads = 0;
+    let mut cores = BTreeSet::new();
+    let mut sockets = BTreeSet::new();
+
+    for cpu in root.read_dir("/sys/devices/system/cpu")? {
+        if !is_cpu_dir(&cpu) {
+            continue;
+        }
+        let topology = |file: &str| {
+            root.read_to_string(format!("/sys/devices/system/cpu/{}/topology/{}", cpu, file))
+                .map(|id| id.trim().to_string())
+        };
+        // Offline CPUs have no topology.
+        let (Ok(package), Ok(core_id)) = (topology("physical_package_id"), topology("core_id"))
+        else {
+            continue;
+        };
+        // A core is the set of its threads, e.g. "0,4". The `core_id` is
+        // not unique on ARM, where it starts again in every cluster, all
+        // of them in package 0. Kernels before 5.7 name the set
+        // `thread_siblings_list`.
+        let core = topology("core_cpus_list")
+            .or_else(|_| topology("thread_siblings_list"))
+            .unwrap_or_else(|_| format!("{}:{}", package, core_id));
+        threads += 1;
+        cores.insert(core);
+        sockets.insert(package);
+    }
+
+    if threads == 0 {
+        return Err(FetchError::NotFound("no CPU topology in sysfs".to_string()));
+    }
+    Ok((threads, cores.len() as u32, sockets.len() as u32))
+}
+
+/// The number of threads, cores and sockets, from the IDs in the blocks
+/// of `/proc/cpuinfo`, which only x86 lists.
+fn cpuinfo_topology(processors: &[&HashMap<&str, &str>]) -> Option<(u32, u32, u32)> {
+    let ids: BTreeSet<(&str, &str)> = processors
+        .iter()
+        .map(|block| Some((*block.get("physical id")?, *block.get("core id")?)))
+        .collect::<Option<_>>()?;
+    let sockets: BTreeSet<&str> = ids.iter().map(|(package, _)| *package).collect();
+    Some((
+        processors.len() as u32,
+        ids.len() as u32,
+        sockets.len() as u32,
+    ))
+}
+
+/// The highest maximum frequency of any CPU, in MHz.
+fn max_freq_mhz(opts: &Options) -> Option<u32> {
+    let root = opts.root();
+    root.read_dir("/sys/devices/system/cpu")
+        .ok()?
+        .iter()
+        .filter(|cpu| is_cpu_dir(cpu))
+        .filter_map(|cpu| {
+            root.read_to_string(format!(
+                "/sys/devices/system/cpu/{}/cpufreq/cpuinfo_max_freq",
+                cpu
+            ))
+            .ok()?
+            .trim()
+            .parse::<u32>()
+            .ok()
+        })
+        .max()
+        .map(|khz| khz / 1000)
+}
+
+/// Whether `name` is the directory of a CPU in sysfs, e.g. "cpu0", as
+/// opposed to e.g. "cpufreq".
+fn is_cpu_dir(name: &str) -> bool {
+    name.strip_prefix("cpu")
+        .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    fn detect(fixture: Fixture) -> CpuInfo {
+        read(&Options::default().with_root(fixture.root())).unwrap()
+    }
+
+    #[test]
+    fn x86_from_cpuinfo() {
+        let block = |processor: u32, core: u32| {
+            format!(
+                "processor\t: {}\nmodel name\t: Intel(R) Core(TM) i7-8565U CPU @ 1.80GHz\n\
+                 physical id\t: 0\ncore id\t\t: {}\n\n",
+                processor, core
+            )
+        };
+        let cpuinfo: String = (0..8).map(|cpu| block(cpu, cpu % 4)).collect();
+        let fixture = Fixture::new().file("/proc/cpuinfo", &cpuinfo).file(
+            "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_max_freq",
+            "4600000\n",
+        );
+
+        assert_eq!(
+            detect(fixture).to_string(),
+            "Intel Core i7-8565U (4C/8T) @ 4.60 GHz"
+        );
+    }
+
+    #[test]
+    fn arm_big_little_from_sysfs() {
+        let block = |processor: u32, part: &str| {
+            format!(
+                "processor\t: {}\nBogoMIPS\t: 48.00\nCPU implementer\t: 0x41\nCPU part\t: {}\n\n",
+                processor, part
+            )
+        };
+        let mut cpuinfo: String = (0..4).map(|cpu| block(cpu, "0xd05")).collect();
+        cpuinfo += &(4..8).map(|cpu| block(cpu, "0xd0b")).collect::<String>();
+        cpuinfo += "Hardware\t: Rockchip RK3588\n";
+
+        // The RK3588 has a cluster of four Cortex-A55, and two clusters of
+        // two Cortex-A76, with `core_id` starting again in each of them.
+        let sysfs = |siblings: &str| {
+            let mut fixture = Fixture::new().file("/proc/cpuinfo", &cpuinfo);
+            for (cpu, core_id) in [0, 1, 2, 3, 0, 1, 0, 1].into_iter().enumerate() {
+                let topology = format!("/sys/devices/system/cpu/cpu{}/topology", cpu);
+                fixture = fixture
+                    .file(&format!("{}/physical_package_id", topology), "0\n")
+                    .file(&format!("{}/core_id", topology), &format!("{}\n", core_id))
+                    .file(&format!("{}/{}", topology, siblings), &format!("{}\n", cpu));
+            }
+            fixture
+        };
+
+        let cpu = detect(sysfs("core_cpus_list"));
+        assert_eq!(cpu.model, "ARM Cortex-A55 + Cortex-A76");
+        assert_eq!((cpu.cores, cpu.threads, cpu.sockets), (Some(8), 8, Some(1)));
+        assert_eq!(cpu.to_string(), "ARM Cortex-A55 + Cortex-A76 (8)");
+
+        let cpu = detect(sysfs("thread_siblings_list"));
+        assert_eq!((cpu.cores, cpu.threads, cpu.sockets), (Some(8), 8, Some(1)));
+    }
+
+    #[test]
+    fn risc_v_uarch() {
+        let fixture = Fixture::new().file(
+            "/proc/cpuinfo",
+            "processor\t: 0\nhart\t\t: 1\nisa\t\t: rv64imafdc\nuarch\t\t: sifive,u74-mc\n\n",
+        );
+        assert_eq!(detect(fixture).model, "sifive u74-mc");
+    }
+}
@@ -0,0 +1,73 @@
+// @Brief : This file contains the logic to run probes concurrently,
+//          each in its own thread, with a deadline after which its
+//          result is no longer waited for.
+
+use crate::FetchError;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A probe running in the background, see [`spawn`].
+pub(crate) struct Pending<T> {
+    name: String,
+    rx: Receiver<Result<T, FetchError>>,
+    deadline: Instant,
+}
+
+/// Start running `probe` for the field `name` in a new thread, which is
+/// given `timeout` from now to finish.
+pub(crate) fn spawn<T, F>(name: &str, timeout: Duration, probe: F) -> Pending<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, FetchError> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let deadline = Instant::now() + timeout;
+
+    // The thread is detached, so that a probe which never finishes, can
+    // not keep the others, or `fetchit` itself from finishing. Sending
+    // fails once nobody is waiting for the result anymore, which is fine.
+    thread::spawn(move || {
+        let _ = tx.send(probe());
+    });
+
+    Pending {
+        name: name.to_string(),
+        rx,
+        deadline,
+    }
+}
+
+impl<T> Pending<T> {
+    /// Wait for the probe to finish, and return its result, or a
+    /// [`FetchError::Timeout`] if it is still running once its deadline
+    /// has passed.
+    pub(crate) fn wait(self) -> Result<T, FetchError> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        self.rx.recv_timeout(remaining).unwrap_or_else(|_| {
+            Err(FetchError::Timeout(format!(
+                "{} did not finish in time",
+                self.name
+            )))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_probe_times_out() {
+        let slow = spawn("slow", Duration::from_millis(20), || {
+            thread::sleep(Duration::from_secs(5));
+            Ok(1)
+        });
+        let fast = spawn("fast", Duration::from_millis(500), || Ok(2));
+
+        let start = Instant::now();
+        assert!(matches!(slow.wait(), Err(FetchError::Timeout(_))));
+        assert_eq!(fast.wait(), Ok(2));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,249 @@
+// @Brief : This file contains the disk usage of the mounted filesystems,
+//          from `/proc/self/mountinfo` and `statvfs(3)`.
+
+use crate::{FetchError, Options, SizeUnit};
+use std::ffi::CString;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+
+/// The usage of the filesystem mounted on a mount point, in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disk {
+    /// Where the filesystem is mounted, e.g. "/home".
+    pub mount_point: String,
+    /// Device, or other source, of the filesystem, e.g. "/dev/nvme0n1p2".
+    pub source: String,
+    /// Type of the filesystem, e.g. "ext4", or "btrfs".
+    pub fs_type: String,
+    /// Space in use, i.e. the size without the free blocks.
+    pub used: u64,
+    /// Size of the filesystem.
+    pub total: u64,
+    /// Space available to unprivileged users, which leaves out the
+    /// blocks reserved for root.
+    pub available: u64,
+}
+
+impl Disk {
+    /// The share of the space in use, in percent. Just like `df`, the
+    /// blocks reserved for root are left out, and it is rounded up.
+    pub fn percent(&self) -> u64 {
+        let usable = self.used + self.available;
+        (self.used * 100 + usable.saturating_sub(1))
+            .checked_div(usable)
+            .unwrap_or(0)
+    }
+}
+
+impl fmt::Display for Disk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} / {} ({}%, {}) on {}",
+            SizeUnit::Gib.format(self.used),
+            SizeUnit::Gib.format(self.total),
+            self.percent(),
+            self.fs_type,
+            self.mount_point
+        )
+    }
+}
+
+/// Filesystems, that are not backed by any storage, and so have no use
+/// for a disk usage.
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "proc",
+    "pstore",
+    "ramfs",
+    "rpc_pipefs",
+    "securityfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+/// A line of `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mount {
+    mount_point: String,
+    fs_type: String,
+    source: String,
+}
+
+/// Read the usage of the filesystems mounted on the mount points from
+/// `opts`, in that order, skipping the ones, that are not mounted, or
+/// are pseudo filesystems. Overlay filesystems, e.g. the root of a
+/// container, are only included, if `opts` asks for them.
+pub fn read(opts: &Options) -> Result<Vec<Disk>, FetchError> {
+    let mounts = parse_mountinfo(&crate::read_traced(opts, "/proc/self/mountinfo")?);
+
+    let disks: Vec<Disk> = opts
+        .disks()
+        .iter()
+        .filter_map(|mount_point| {
+            let mount_point = match mount_point.trim_end_matches('/') {
+                "" => "/",
+                mount_point => mount_point,
+            };
+            // Filesystems mounted on top of each other are listed in the
+            // order they were mounted, so the last one is visible.
+            let mount = mounts
+                .iter()
+                .rfind(|mount| mount.mount_point == mount_point)?;
+            if PSEUDO_FILESYSTEMS.contains(&mount.fs_type.as_str())
+                || (mount.fs_type == "overlay" && !opts.disk_overlay())
+            {
+                return None;
+            }
+
+            let stats = opts
+                .attempt(&format!("statvfs {}", mount_point), || {
+                    statvfs(opts, mount_point)
+                })
+                .ok()?;
+            Some(Disk {
+                source: mount.source.clone(),
+                fs_type: mount.fs_type.clone(),
+                ..stats
+            })
+        })
+        .collect();
+
+    if disks.is_empty() {
+        return Err(FetchError::NotFound(format!(
+            "no disk is mounted on {}",
+            opts.disks().join(", ")
+        )));
+    }
+    Ok(disks)
+}
+
+/// Parse `/proc/self/mountinfo`, e.g. "36 35 98:0 / /home rw,noatime
+/// shared:1 - ext4 /dev/sda2 rw", into its mounts.
+fn parse_mountinfo(mountinfo: &str) -> Vec<Mount> {
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // The optional fields, e.g. "shared:1", end at the separator.
+            let (fields, rest) = line.split_once(" - ")?;
+            let mount_point = fields.split(' ').nth(4)?;
+            let mut rest = rest.split(' ');
+            Some(Mount {
+                mount_point: crate::unescape_octal(mount_point),
+                fs_type: rest.next()?.to_string(),
+                source: crate::unescape_octal(rest.next()?),
+            })
+        })
+        .collect()
+}
+
+/// Call `statvfs(3)` for `mount_point`, under the root from `opts`, for
+/// the sizes of a `Disk`.
+fn statvfs(opts: &Options, mount_point: &str) -> Result<Disk, FetchError> {
+    let path = opts.root().path(mount_point);
+    let invalid = |_| FetchError::ParseError(format!("{} contains a NUL", mount_point));
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(invalid)?;
+    let mut buf = MaybeUninit::<libc::statvfs>::zeroed();
+
+    // SAFETY: `c_path` is NUL terminated, and `buf` is a valid, writable
+    // `statvfs`, which is filled on success.
+    let buf = unsafe {
+        if libc::statvfs(c_path.as_ptr(), buf.as_mut_ptr()) != 0 {
+            return Err(FetchError::from_io(
+                &path.to_string_lossy(),
+                std::io::Error::last_os_error(),
+            ));
+        }
+        buf.assume_init()
+    };
+
+    let block = buf.f_frsize as u64;
+    Ok(Disk {
+        mount_point: mount_point.to_string(),
+        source: String::new(),
+        fs_type: String::new(),
+        used: (buf.f_blocks as u64).saturating_sub(buf.f_bfree as u64) * block,
+        total: buf.f_blocks as u64 * block,
+        available: buf.f_bavail as u64 * block,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    const MOUNTINFO: &str = "\
+22 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+23 22 0:21 / /proc rw,nosuid shared:12 - proc proc rw
+24 22 0:22 / /tmp rw shared:13 - tmpfs tmpfs rw,size=8G
+25 22 259:3 / /home rw,relatime shared:2 - btrfs /dev/nvme0n1p3 rw,subvol=/home
+26 22 8:17 / /media/USB\\040Stick rw - vfat /dev/sdb1 rw
+";
+
+    #[test]
+    fn mountinfo() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        assert_eq!(mounts.len(), 5);
+        assert_eq!(
+            mounts[3],
+            Mount {
+                mount_point: "/home".to_string(),
+                fs_type: "btrfs".to_string(),
+                source: "/dev/nvme0n1p3".to_string(),
+            }
+        );
+        assert_eq!(mounts[4].mount_point, "/media/USB Stick");
+    }
+
+    #[test]
+    fn filters_and_statvfs() {
+        let fixture = Fixture::new()
+            .file("/proc/self/mountinfo", MOUNTINFO)
+            .dir("/home")
+            .dir("/tmp");
+        let disks = |mount_points: &[&str]| {
+            let opts = Options::default()
+                .with_root(fixture.root())
+                .with_disks(mount_points.iter().map(|point| point.to_string()).collect());
+            read(&opts)
+        };
+
+        // The fixture lives on whatever the temporary directory is, so
+        // only the mounts, and not the sizes, are known.
+        let found = disks(&["/", "/proc", "/tmp", "/home", "/srv"]).unwrap();
+        let names: Vec<_> = found
+            .iter()
+            .map(|disk| (disk.mount_point.as_str(), disk.fs_type.as_str()))
+            .collect();
+        assert_eq!(names, [("/", "ext4"), ("/home", "btrfs")]);
+        assert!(found[0].total > 0);
+        assert!(found[0].used <= found[0].total);
+
+        assert!(disks(&["/tmp"]).unwrap_err().is_not_found());
+
+        let overlay = Fixture::new().file(
+            "/proc/self/mountinfo",
+            "500 400 0:50 / / rw - overlay overlay rw,lowerdir=/a,upperdir=/b\n",
+        );
+        let opts = Options::default().with_root(overlay.root());
+        assert!(read(&opts).unwrap_err().is_not_found());
+        let disks = read(&opts.with_disk_overlay(true)).unwrap();
+        assert_eq!(disks[0].fs_type, "overlay");
+    }
+}
@@ -0,0 +1,102 @@
+// @Brief : This file contains `FetchError`, the error returned by every
+//          probe, when it can not determine its value.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Why a probe could not determine its value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchError {
+    /// None of the sources for the value exist on this system, e.g. the
+    /// file is missing, or the command is not installed.
+    NotFound(String),
+    /// An external command ran, but exited with an error.
+    CommandFailed(String),
+    /// A source exists, but its contents could not be understood.
+    ParseError(String),
+    /// The probe did not finish in time.
+    Timeout(String),
+    /// A source exists, but is not readable by the current user.
+    PermissionDenied(String),
+}
+
+impl FetchError {
+    /// Convert an I/O error, that happened while accessing `what`, e.g. a
+    /// path or a command, into the matching variant.
+    pub fn from_io(what: &str, err: io::Error) -> Self {
+        let message = format!("{}: {}", what, err);
+        match err.kind() {
+            io::ErrorKind::NotFound => FetchError::NotFound(message),
+            io::ErrorKind::PermissionDenied => FetchError::PermissionDenied(message),
+            io::ErrorKind::TimedOut => FetchError::Timeout(message),
+            io::ErrorKind::InvalidData => FetchError::ParseError(message),
+            _ => FetchError::CommandFailed(message),
+        }
+    }
+
+    /// Whether the value is simply absent on this system, as opposed to
+    /// one of its sources being broken.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, FetchError::NotFound(_))
+    }
+
+    /// Pick the more useful of two errors from a chain of fallbacks, i.e.
+    /// the first source, that is actually broken, over a missing one.
+    pub(crate) fn or(self, other: FetchError) -> FetchError {
+        if self.is_not_found() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::NotFound(msg) => write!(f, "not found: {}", msg),
+            FetchError::CommandFailed(msg) => write!(f, "command failed: {}", msg),
+            FetchError::ParseError(msg) => write!(f, "parse error: {}", msg),
+            FetchError::Timeout(msg) => write!(f, "timed out: {}", msg),
+            FetchError::PermissionDenied(msg) => write!(f, "permission denied: {}", msg),
+        }
+    }
+}
+
+impl Error for FetchError {}
+
+/// Chaining of fallback sources, that keeps the most useful error.
+pub(crate) trait ResultExt<T> {
+    /// Try `fallback`, if `self` is an error.
+    fn or_try<F>(self, fallback: F) -> Result<T, FetchError>
+    where
+        F: FnOnce() -> Result<T, FetchError>;
+}
+
+impl<T> ResultExt<T> for Result<T, FetchError> {
+    fn or_try<F>(self, fallback: F) -> Result<T, FetchError>
+    where
+        F: FnOnce() -> Result<T, FetchError>,
+    {
+        match self {
+            Ok(value) => Ok(value),
+            Err(err) => fallback().map_err(|other| err.or(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broken_source_wins() {
+        let missing = || Err(FetchError::NotFound("missing".to_string()));
+        let broken = || Err(FetchError::ParseError("broken".to_string()));
+
+        let result: Result<u32, _> = missing().or_try(broken).or_try(missing);
+        assert_eq!(result, Err(FetchError::ParseError("broken".to_string())));
+        assert_eq!(missing().or_try(|| Ok(1)), Ok(1));
+    }
+}
@@ -0,0 +1,190 @@
+// @Brief : This file contains the detection of the GPUs, from the display
+//          controllers on the PCI bus, named after `pci.ids`.
+
+use crate::{pci_ids, FetchError, Options};
+use std::fmt;
+
+/// A display controller on the PCI bus, e.g. the integrated GPU of the
+/// CPU, or a discrete one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gpu {
+    /// Address on the PCI bus, e.g. "0000:01:00.0".
+    pub slot: String,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    /// Name of the vendor, from `pci.ids`, e.g. "NVIDIA Corporation".
+    pub vendor: Option<String>,
+    /// Name of the device, from `pci.ids`, e.g. "GA107M [GeForce RTX 3050
+    /// Mobile]".
+    pub device: Option<String>,
+    /// The kernel driver bound to the device, e.g. "i915", or "nvidia".
+    pub driver: Option<String>,
+}
+
+impl fmt::Display for Gpu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let vendor = match (
+            VENDORS.iter().find(|(id, _)| *id == self.vendor_id),
+            &self.vendor,
+        ) {
+            (Some((_, short)), _) => short.to_string(),
+            (None, Some(vendor)) => vendor.clone(),
+            (None, None) => format!("Vendor {:04x}", self.vendor_id),
+        };
+        // The marketing name is in brackets, after the code name of the
+        // chip, e.g. "Navi 21 [Radeon RX 6800/6800 XT / 6900 XT]".
+        let device = match &self.device {
+            Some(device) => match device.rsplit_once('[') {
+                Some((_, name)) if name.ends_with(']') => name.trim_end_matches(']').to_string(),
+                _ => device.clone(),
+            },
+            None => format!("Device {:04x}", self.device_id),
+        };
+
+        write!(f, "{} {}", vendor, device)?;
+        if let Some(driver) = &self.driver {
+            write!(f, " [{}]", driver)?;
+        }
+        Ok(())
+    }
+}
+
+/// Short names of the vendors of most GPUs, whose full names in `pci.ids`
+/// are a mouthful, e.g. "Advanced Micro Devices, Inc. [AMD/ATI]".
+const VENDORS: &[(u16, &str)] = &[
+    (0x1002, "AMD"),
+    (0x1022, "AMD"),
+    (0x10de, "NVIDIA"),
+    (0x8086, "Intel"),
+    (0x1af4, "Virtio"),
+    (0x15ad, "VMware"),
+    (0x80ee, "VirtualBox"),
+    (0x1234, "QEMU"),
+    (0x1414, "Microsoft"),
+    (0x1a03, "ASPEED"),
+    (0x102b, "Matrox"),
+];
+
+/// The PCI class of display controllers, i.e. VGA, XGA, 3D and others.
+const DISPLAY_CLASS: u32 = 0x03;
+
+/// Find the display controllers on the PCI bus, in the order of their
+/// slots, which usually puts the integrated GPU first.
+pub fn read(opts: &Options) -> Result<Vec<Gpu>, FetchError> {
+    let root = opts.root();
+    let devices = opts.attempt("/sys/bus/pci/devices", || {
+        root.read_dir("/sys/bus/pci/devices")
+    })?;
+
+    let hex = |path: &str| -> Option<u32> {
+        let value = crate::read_traced(opts, path).ok()?;
+        u32::from_str_radix(value.trim().trim_start_matches("0x"), 16).ok()
+    };
+    let mut gpus: Vec<Gpu> = devices
+        .into_iter()
+        .filter_map(|slot| {
+            let dir = format!("/sys/bus/pci/devices/{}", slot);
+            // e.g. "0x030000", the class, subclass and programming interface.
+            let class = hex(&format!("{}/class", dir))?;
+            if class >> 16 != DISPLAY_CLASS {
+                return None;
+            }
+
+            let driver_link = format!("{}/driver", dir);
+            let driver = opts
+                .attempt(&driver_link, || root.read_link(&driver_link))
+                .ok()
+                .and_then(|driver| Some(driver.file_name()?.to_string_lossy().into_owned()));
+            Some(Gpu {
+                vendor_id: hex(&format!("{}/vendor", dir))? as u16,
+                device_id: hex(&format!("{}/device", dir))? as u16,
+                slot,
+                vendor: None,
+                device: None,
+                driver,
+            })
+        })
+        .collect();
+    if gpus.is_empty() {
+        return Err(FetchError::NotFound(
+            "no display controller on the PCI bus".to_string(),
+        ));
+    }
+
+    // Without the database, the IDs are all there is to display.
+    if let Ok(ids) = pci_ids::read(opts) {
+        for gpu in &mut gpus {
+            let name = pci_ids::lookup(&ids, gpu.vendor_id, gpu.device_id);
+            gpu.vendor = name.vendor;
+            gpu.device = name.device;
+        }
+    }
+    Ok(gpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+    use std::os::unix::fs::symlink;
+
+    fn pci_device(
+        fixture: Fixture,
+        slot: &str,
+        class: &str,
+        vendor: &str,
+        device: &str,
+    ) -> Fixture {
+        let dir = format!("/sys/bus/pci/devices/{}", slot);
+        fixture
+            .file(&format!("{}/class", dir), &format!("{}\n", class))
+            .file(&format!("{}/vendor", dir), &format!("{}\n", vendor))
+            .file(&format!("{}/device", dir), &format!("{}\n", device))
+    }
+
+    #[test]
+    fn hybrid_laptop() {
+        let fixture = Fixture::new();
+        let fixture = pci_device(fixture, "0000:00:00.0", "0x060000", "0x8086", "0x9a14");
+        let fixture = pci_device(fixture, "0000:00:02.0", "0x030000", "0x8086", "0x9a49");
+        let fixture = pci_device(fixture, "0000:01:00.0", "0x030200", "0x10de", "0x25a2")
+            .file(
+                "/usr/share/misc/pci.ids",
+                "10de  NVIDIA Corporation\n\t25a2  GA107M [GeForce RTX 3050 Mobile]\n\
+                 8086  Intel Corporation\n\t9a49  TigerLake-LP GT2 [Iris Xe Graphics]\n",
+            )
+            .dir("/sys/bus/pci/drivers/i915");
+        symlink(
+            fixture.root().path("/sys/bus/pci/drivers/i915"),
+            fixture
+                .root()
+                .path("/sys/bus/pci/devices/0000:00:02.0/driver"),
+        )
+        .unwrap();
+
+        let opts = Options::default().with_root(fixture.root());
+        let gpus: Vec<String> = read(&opts).unwrap().iter().map(Gpu::to_string).collect();
+        assert_eq!(
+            gpus,
+            [
+                "Intel Iris Xe Graphics [i915]",
+                "NVIDIA GeForce RTX 3050 Mobile"
+            ]
+        );
+
+        // Without `pci.ids`, the IDs are displayed instead.
+        let fixture = pci_device(
+            Fixture::new(),
+            "0000:03:00.0",
+            "0x030000",
+            "0x1002",
+            "0x73bf",
+        );
+        let gpus = read(&Options::default().with_root(fixture.root())).unwrap();
+        assert_eq!(gpus[0].to_string(), "AMD Device 73bf");
+
+        assert!(read(&Options::default().with_root(Fixture::new().root()))
+            .unwrap_err()
+            .is_not_found());
+    }
+}
@@ -0,0 +1,155 @@
+// @Brief : This file contains the hardware model of the machine, from the
+//          DMI tables, or the device tree on ARM boards.
+
+use crate::error::ResultExt;
+use crate::{FetchError, Options};
+use std::fmt;
+
+/// The hardware model, e.g. "LENOVO 20XW ThinkPad X1 Carbon Gen 9", or
+/// "Raspberry Pi 4 Model B Rev 1.4".
+///
+/// Placeholders left by the firmware, e.g. "To Be Filled By O.E.M.", are
+/// `None`, just like missing values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostModel {
+    /// `sys_vendor`, e.g. "LENOVO".
+    pub vendor: Option<String>,
+    /// `product_name`, or the device tree model, e.g. "20XW".
+    pub product: Option<String>,
+    /// `product_version`, which some vendors use for the actual model,
+    /// e.g. "ThinkPad X1 Carbon Gen 9".
+    pub version: Option<String>,
+    /// `board_name`, the motherboard, e.g. "B550 AORUS ELITE".
+    pub board: Option<String>,
+    /// `bios_version`, e.g. "N32ET86W (1.62 )".
+    pub bios_version: Option<String>,
+}
+
+impl fmt::Display for HostModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Custom built desktops often only describe their motherboard.
+        let product = self.product.as_deref().or(self.board.as_deref());
+        // Skip the vendor, or the version, when the product already
+        // names them, e.g. "HP EliteBook 840 G8" from "HP".
+        let named = |part: &&str| product.is_none_or(|product| !product.contains(part));
+        let words: Vec<&str> = [self.vendor.as_deref().filter(named), product]
+            .into_iter()
+            .chain([self.version.as_deref().filter(named)])
+            .flatten()
+            .collect();
+        f.write_str(&words.join(" "))
+    }
+}
+
+/// Values, that firmware vendors forgot to fill in, compared without
+/// regard to case.
+const PLACEHOLDERS: &[&str] = &[
+    "To Be Filled By O.E.M.",
+    "To Be Filled By OEM",
+    "Default string",
+    "System manufacturer",
+    "System Product Name",
+    "System Version",
+    "System Serial Number",
+    "Not Applicable",
+    "Not Specified",
+    "Type1ProductConfigId",
+    "O.E.M.",
+    "OEM",
+    "None",
+    "Unknown",
+    "Undefined",
+    "INVALID",
+    "Default",
+    "x.x",
+    "0123456789",
+];
+
+/// Read the hardware model from `/sys/class/dmi/id`, or else from
+/// `/proc/device-tree/model`, for boards without DMI.
+pub fn read(opts: &Options) -> Result<HostModel, FetchError> {
+    let dmi = |file: &str| {
+        crate::read_traced(opts, &format!("/sys/class/dmi/id/{}", file))
+            .ok()
+            .and_then(|value| clean(&value))
+    };
+    let host = HostModel {
+        vendor: dmi("sys_vendor"),
+        product: dmi("product_name"),
+        version: dmi("product_version"),
+        board: dmi("board_name"),
+        bios_version: dmi("bios_version"),
+    };
+    if host.product.is_some() || host.board.is_some() {
+        return Ok(host);
+    }
+
+    // The device tree holds a single NUL terminated model string.
+    let model = crate::read_traced(opts, "/proc/device-tree/model")
+        .or_try(|| crate::read_traced(opts, "/sys/firmware/devicetree/base/model"))?;
+    let product = clean(&model)
+        .ok_or_else(|| FetchError::NotFound("the device tree has no model".to_string()))?;
+    Ok(HostModel {
+        product: Some(product),
+        ..HostModel::default()
+    })
+}
+
+/// Trim a value, and discard it, if it is empty, or a placeholder.
+fn clean(value: &str) -> Option<String> {
+    let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    let placeholder = value.is_empty()
+        || PLACEHOLDERS
+            .iter()
+            .any(|placeholder| placeholder.eq_ignore_ascii_case(value));
+    (!placeholder).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    fn detect(fixture: &Fixture) -> Result<HostModel, FetchError> {
+        read(&Options::default().with_root(fixture.root()))
+    }
+
+    #[test]
+    fn dmi_and_device_tree() {
+        let thinkpad = Fixture::new()
+            .file("/sys/class/dmi/id/sys_vendor", "LENOVO\n")
+            .file("/sys/class/dmi/id/product_name", "20XW\n")
+            .file(
+                "/sys/class/dmi/id/product_version",
+                "ThinkPad X1 Carbon Gen 9\n",
+            );
+        assert_eq!(
+            detect(&thinkpad).unwrap().to_string(),
+            "LENOVO 20XW ThinkPad X1 Carbon Gen 9"
+        );
+
+        let hp = Fixture::new()
+            .file("/sys/class/dmi/id/sys_vendor", "HP\n")
+            .file("/sys/class/dmi/id/product_name", "HP EliteBook 840 G8\n");
+        assert_eq!(detect(&hp).unwrap().to_string(), "HP EliteBook 840 G8");
+
+        let custom_build = Fixture::new()
+            .file("/sys/class/dmi/id/sys_vendor", "To Be Filled By O.E.M.\n")
+            .file("/sys/class/dmi/id/product_name", "To be filled by O.E.M.\n")
+            .file("/sys/class/dmi/id/board_name", "B550 AORUS ELITE\n");
+        let host = detect(&custom_build).unwrap();
+        assert_eq!(host.vendor, None);
+        assert_eq!(host.to_string(), "B550 AORUS ELITE");
+
+        let raspberry_pi = Fixture::new().file(
+            "/proc/device-tree/model",
+            "Raspberry Pi 4 Model B Rev 1.4\0",
+        );
+        assert_eq!(
+            detect(&raspberry_pi).unwrap().to_string(),
+            "Raspberry Pi 4 Model B Rev 1.4"
+        );
+
+        assert!(detect(&Fixture::new()).unwrap_err().is_not_found());
+    }
+}
@@ -0,0 +1,95 @@
+// @Brief : This file contains a safe wrapper around `getifaddrs(3)`, which
+//          lists the addresses of the network interfaces without `ip`.
+
+use std::ffi::CStr;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ptr;
+
+/// An address of a network interface, as reported by `getifaddrs(3)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IfAddr {
+    /// Name of the interface, e.g. "wlan0".
+    pub name: String,
+    /// The `IFF_*` flags of the interface, e.g. `IFF_UP`.
+    pub flags: u32,
+    pub address: IpAddr,
+    /// Length of the network prefix, from the netmask, e.g. 24.
+    pub prefix_len: u8,
+}
+
+/// Call `getifaddrs(3)` for the IPv4 and IPv6 addresses of every
+/// interface, leaving out the entries of other address families.
+pub(crate) fn getifaddrs() -> io::Result<Vec<IfAddr>> {
+    let mut head: *mut libc::ifaddrs = ptr::null_mut();
+
+    // SAFETY: on success, `head` points to a linked list, owned by libc,
+    // until it is released with `freeifaddrs`.
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut addrs = Vec::new();
+    let mut entry = head;
+    while !entry.is_null() {
+        // SAFETY: `entry` is a node of the list, which is still alive.
+        let ifa = unsafe { &*entry };
+        entry = ifa.ifa_next;
+
+        // SAFETY: the address, and the netmask, are either null, or point
+        // to a `sockaddr` of the family in `sa_family`, and the name is a
+        // NUL terminated string.
+        let (address, netmask, name) = unsafe {
+            (
+                sockaddr_ip(ifa.ifa_addr),
+                sockaddr_ip(ifa.ifa_netmask),
+                CStr::from_ptr(ifa.ifa_name),
+            )
+        };
+        let Some(address) = address else {
+            continue;
+        };
+
+        let prefix_len = match netmask {
+            Some(IpAddr::V4(mask)) => u32::from(mask).count_ones() as u8,
+            Some(IpAddr::V6(mask)) => u128::from(mask).count_ones() as u8,
+            None => 0,
+        };
+        addrs.push(IfAddr {
+            name: name.to_string_lossy().into_owned(),
+            flags: ifa.ifa_flags,
+            address,
+            prefix_len,
+        });
+    }
+
+    // SAFETY: `head` came from `getifaddrs`, and no reference into the
+    // list outlives this call.
+    unsafe { libc::freeifaddrs(head) };
+    Ok(addrs)
+}
+
+/// The IP address in `addr`, if it is an IPv4, or IPv6 one.
+///
+/// # Safety
+///
+/// `addr` must be null, or point to a `sockaddr` of the size, that
+/// matches its `sa_family`.
+unsafe fn sockaddr_ip(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    if addr.is_null() {
+        return None;
+    }
+    match i32::from((*addr).sa_family) {
+        libc::AF_INET => {
+            let addr = &*(addr as *const libc::sockaddr_in);
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                addr.sin_addr.s_addr,
+            ))))
+        }
+        libc::AF_INET6 => {
+            let addr = &*(addr as *const libc::sockaddr_in6);
+            Some(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,199 @@
+// @Brief : This file contains the detection of the init system, i.e.
+//          the process running as PID 1, e.g. systemd or OpenRC.
+
+use crate::{FetchError, Options};
+use std::fmt;
+use std::path::Path;
+
+/// The init system of the machine, e.g. "systemd 252".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitSystem {
+    /// Name of the init system, e.g. "systemd", or "OpenRC".
+    pub name: String,
+    /// Its version, if it can be read without root, e.g. "252".
+    pub version: Option<String>,
+}
+
+impl fmt::Display for InitSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} {}", self.name, version),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// The init systems, `fetchit` knows about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Init {
+    Systemd,
+    OpenRc,
+    Runit,
+    S6,
+    Dinit,
+    SysVinit,
+    Busybox,
+}
+
+impl Init {
+    fn name(self) -> &'static str {
+        match self {
+            Init::Systemd => "systemd",
+            Init::OpenRc => "OpenRC",
+            Init::Runit => "runit",
+            Init::S6 => "s6",
+            Init::Dinit => "dinit",
+            Init::SysVinit => "SysVinit",
+            Init::Busybox => "BusyBox",
+        }
+    }
+
+    /// The command, that prints the version of the init system.
+    fn version_command(self) -> Option<(&'static str, &'static [&'static str])> {
+        match self {
+            Init::Systemd => Some(("systemctl", &["--version"])),
+            Init::OpenRc => Some(("openrc", &["--version"])),
+            Init::Dinit => Some(("dinit", &["--version"])),
+            Init::Busybox => Some(("busybox", &["--help"])),
+            Init::Runit | Init::S6 | Init::SysVinit => None,
+        }
+    }
+}
+
+/// Directories, or sockets, that only exist while the init system, or
+/// the service manager, is running.
+const MARKERS: &[(&str, Init)] = &[
+    ("/run/systemd/system", Init::Systemd),
+    ("/run/openrc", Init::OpenRc),
+    ("/run/runit", Init::Runit),
+    ("/run/s6", Init::S6),
+    ("/run/s6-rc", Init::S6),
+    ("/run/dinitctl", Init::Dinit),
+];
+
+/// Detect the init system, from the name of PID 1, and the path of its
+/// executable, or else from the marker directories, it leaves in `/run`.
+pub fn read(opts: &Options) -> Result<InitSystem, FetchError> {
+    let root = opts.root();
+    let comm = crate::read_traced(opts, "/proc/1/comm").map(|comm| comm.trim().to_string());
+    // Following the link usually needs root, in which case the name of
+    // the process is all there is to go on.
+    let exe = opts.attempt("/proc/1/exe", || {
+        root.read_link("/proc/1/exe")
+            .map(|exe| exe.to_string_lossy().into_owned())
+    });
+
+    let names: Vec<&str> = [comm.as_deref().ok(), exe.as_deref().ok()]
+        .into_iter()
+        .flatten()
+        .filter_map(|name| Path::new(name).file_name()?.to_str())
+        .collect();
+    let named = |init: Init| -> bool {
+        names.iter().any(|&name| match init {
+            Init::Systemd => name == "systemd",
+            Init::OpenRc => name == "openrc-init",
+            Init::Runit => name == "runit" || name == "runit-init",
+            Init::S6 => name == "s6-svscan" || name == "s6-linux-init",
+            Init::Dinit => name == "dinit",
+            Init::SysVinit => name == "init",
+            Init::Busybox => name == "busybox",
+        })
+    };
+
+    // A service manager, e.g. OpenRC on top of BusyBox init on Alpine,
+    // is more telling, than the generic init, that started it.
+    let init = [
+        Init::Systemd,
+        Init::OpenRc,
+        Init::Runit,
+        Init::S6,
+        Init::Dinit,
+    ]
+    .into_iter()
+    .find(|&init| named(init))
+    .or_else(|| {
+        MARKERS
+            .iter()
+            .find(|(marker, _)| root.exists(marker))
+            .map(|&(_, init)| init)
+    })
+    .or_else(|| {
+        [Init::Busybox, Init::SysVinit]
+            .into_iter()
+            .find(|&init| named(init))
+    })
+    .ok_or_else(|| match &comm {
+        Ok(comm) => FetchError::NotFound(format!("PID 1, `{}`, is not a known init", comm)),
+        Err(err) => err.clone(),
+    })?;
+
+    let version = init
+        .version_command()
+        .and_then(|(program, args)| crate::host_command(opts, program, args).ok())
+        .and_then(|output| parse_version(&output));
+
+    Ok(InitSystem {
+        name: init.name().to_string(),
+        version,
+    })
+}
+
+/// The first version number on the first line of `output`, e.g. "252"
+/// for "systemd 252 (252.19-1~deb12u1)", or "1.36.1" for "BusyBox v1.36.1".
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .split_whitespace()
+        .map(|word| word.trim_start_matches('v').trim_end_matches(['.', ',']))
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    fn detect(fixture: &Fixture) -> Result<InitSystem, FetchError> {
+        read(&Options::default().with_root(fixture.root()))
+    }
+
+    #[test]
+    fn detect_from_root() {
+        let systemd = Fixture::new().file("/proc/1/comm", "systemd\n");
+        assert_eq!(detect(&systemd).unwrap().to_string(), "systemd");
+
+        // BusyBox init, running OpenRC, as on Alpine.
+        let alpine = Fixture::new()
+            .file("/proc/1/comm", "init\n")
+            .dir("/run/openrc");
+        assert_eq!(detect(&alpine).unwrap().name, "OpenRC");
+
+        let sysvinit = Fixture::new().file("/proc/1/comm", "init\n");
+        assert_eq!(detect(&sysvinit).unwrap().name, "SysVinit");
+
+        let container = Fixture::new().file("/proc/1/comm", "bash\n");
+        assert!(detect(&container).unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn version_output() {
+        assert_eq!(
+            parse_version("systemd 252 (252.19-1~deb12u1)\n+PAM +AUDIT\n").as_deref(),
+            Some("252")
+        );
+        assert_eq!(
+            parse_version("openrc (OpenRC) 0.45.2 (Gentoo Linux)\n").as_deref(),
+            Some("0.45.2")
+        );
+        assert_eq!(
+            parse_version("BusyBox v1.36.1 (2023-07-27) multi-call binary.\n").as_deref(),
+            Some("1.36.1")
+        );
+        assert_eq!(
+            parse_version("Dinit version 0.17.0.\n").as_deref(),
+            Some("0.17.0")
+        );
+    }
+}
@@ -0,0 +1,194 @@
+// @Brief : This file contains the detailed kernel info, i.e. its release,
+//          flavor, architecture and the build string.
+
+use crate::error::ResultExt;
+use crate::{uname, FetchError, Options};
+use std::fmt;
+use std::str::FromStr;
+
+/// The running kernel, e.g. for the release "6.6.8-zen1-1-zen".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelInfo {
+    /// The full release, e.g. "6.6.8-zen1-1-zen".
+    pub release: String,
+    /// The numeric version at the start of the release, e.g. "6.6.8".
+    pub version: String,
+    /// The rest of the release, i.e. the flavor or local version of the
+    /// distribution, e.g. "zen1-1-zen", or "13-amd64".
+    pub flavor: Option<String>,
+    /// Hardware architecture, e.g. "x86_64".
+    pub arch: Option<String>,
+    /// How the kernel was built, e.g. "#1 SMP PREEMPT_DYNAMIC Debian
+    /// 6.1.55-1 (2023-09-29)".
+    pub build: Option<String>,
+}
+
+/// How much of [`KernelInfo`] the kernel module displays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KernelFormat {
+    /// The release, e.g. "6.1.0-13-amd64".
+    #[default]
+    Short,
+    /// The release, architecture and build, e.g. "6.1.0-13-amd64 x86_64
+    /// #1 SMP PREEMPT_DYNAMIC Debian 6.1.55-1 (2023-09-29)".
+    Full,
+}
+
+impl FromStr for KernelFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "short" => Ok(KernelFormat::Short),
+            "full" => Ok(KernelFormat::Full),
+            _ => Err(format!("`{}` is not one of: short, full", format)),
+        }
+    }
+}
+
+impl KernelInfo {
+    /// Render the kernel info, as much of it as `format` asks for.
+    pub fn display(&self, format: KernelFormat) -> String {
+        match format {
+            KernelFormat::Short => self.release.clone(),
+            KernelFormat::Full => [Some(&self.release), self.arch.as_ref(), self.build.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+impl fmt::Display for KernelInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display(KernelFormat::Short))
+    }
+}
+
+/// Read the info about the kernel, of the system under the root from
+/// `opts`. Only the release is required, the other fields are `None`,
+/// when they can not be determined.
+pub fn read(opts: &Options) -> Result<KernelInfo, FetchError> {
+    let root = opts.root();
+    let host_uname = || {
+        root.is_host()
+            .then(uname::uname)
+            .flatten()
+            .ok_or_else(|| FetchError::NotFound("uname(2) is not available".to_string()))
+    };
+
+    // Get the kernel release from `uname(2)`, which is only meaningful for
+    // the running system. Otherwise, read `/proc/sys/kernel/osrelease`, and
+    // as a last resort, run `uname -r`.
+    let release = opts
+        .attempt("uname(2)", || host_uname().map(|uname| uname.release))
+        .or_try(|| crate::read_traced(opts, "/proc/sys/kernel/osrelease"))
+        .or_try(|| crate::host_command(opts, "uname", &["-r"]))?;
+    let release = release.trim().to_string();
+    let (version, flavor) = parse_release(&release)?;
+
+    let arch = opts
+        .attempt("uname(2) machine", || {
+            host_uname().map(|uname| uname.machine)
+        })
+        .ok();
+
+    // The build string is the part of `/proc/version` starting at the
+    // build number, e.g. "#1 SMP ...", after the compiler and the linker.
+    let build = opts
+        .attempt("/proc/version", || {
+            let proc_version = root.read_to_string("/proc/version")?;
+            parse_build(&proc_version).ok_or_else(|| {
+                FetchError::ParseError("/proc/version has no build string".to_string())
+            })
+        })
+        .or_try(|| {
+            opts.attempt("uname(2) version", || {
+                host_uname().map(|uname| uname.version)
+            })
+        })
+        .ok();
+
+    Ok(KernelInfo {
+        version,
+        flavor,
+        arch,
+        build,
+        release,
+    })
+}
+
+/// Split a kernel release, e.g. "6.1.0-13-amd64", into its numeric
+/// version, i.e. "6.1.0", and its flavor, i.e. "13-amd64".
+fn parse_release(release: &str) -> Result<(String, Option<String>), FetchError> {
+    let end = release
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(release.len());
+    let version = release[..end].trim_end_matches('.');
+    if version.is_empty() {
+        return Err(FetchError::ParseError(format!(
+            "invalid kernel release {:?}",
+            release
+        )));
+    }
+
+    // Drop the separator between the version, and the flavor.
+    let flavor = release[end..].trim_start_matches(['-', '_', '.']);
+    let flavor = (!flavor.is_empty()).then(|| flavor.to_string());
+    Ok((version.to_string(), flavor))
+}
+
+/// The build string from the contents of `/proc/version`, i.e. everything
+/// from the " #<build number>" onward.
+fn parse_build(proc_version: &str) -> Option<String> {
+    let proc_version = proc_version.trim();
+    proc_version
+        .match_indices(" #")
+        .map(|(index, _)| &proc_version[index + 1..])
+        .find(|build| build[1..].starts_with(|c: char| c.is_ascii_digit()))
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    #[test]
+    fn release_and_flavor() {
+        let split = |release| parse_release(release).unwrap();
+        assert_eq!(
+            split("6.1.0-13-amd64"),
+            ("6.1.0".to_string(), Some("13-amd64".to_string()))
+        );
+        assert_eq!(
+            split("6.6.8-zen1-1-zen"),
+            ("6.6.8".to_string(), Some("zen1-1-zen".to_string()))
+        );
+        assert_eq!(split("6.5.0"), ("6.5.0".to_string(), None));
+        assert!(parse_release("zen").is_err());
+    }
+
+    #[test]
+    fn full_format_from_root() {
+        let fixture = Fixture::new()
+            .file("/proc/sys/kernel/osrelease", "6.1.0-13-amd64\n")
+            .file(
+                "/proc/version",
+                "Linux version 6.1.0-13-amd64 (debian-kernel@lists.debian.org) \
+                 (gcc-12 (Debian 12.2.0-14) 12.2.0, GNU ld (GNU Binutils for Debian) 2.40) \
+                 #1 SMP PREEMPT_DYNAMIC Debian 6.1.55-1 (2023-09-29)\n",
+            );
+        let kernel = read(&Options::default().with_root(fixture.root())).unwrap();
+
+        assert_eq!(kernel.version, "6.1.0");
+        assert_eq!(kernel.arch, None);
+        assert_eq!(kernel.display(KernelFormat::Short), "6.1.0-13-amd64");
+        assert_eq!(
+            kernel.display(KernelFormat::Full),
+            "6.1.0-13-amd64 #1 SMP PREEMPT_DYNAMIC Debian 6.1.55-1 (2023-09-29)"
+        );
+    }
+}
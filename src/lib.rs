@@ -2,217 +2,619 @@
 // @Date  : 19th August, 2022
 // @Brief : This is the lib.rs file for `fetchit`
 
+use std::collections::HashMap;
 use std::env; // For getting commandline arguments and reading Environment
               // Variables.
-use std::error::Error;
-use std::fs; // For reading files.
-use std::process::Command; // For exit with a code.
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod battery;
+pub mod cache;
+pub mod command;
+pub mod cpu;
+mod deadline;
+pub mod disk;
+pub mod error;
+pub mod gpu;
+pub mod host;
+mod ifaddrs;
+pub mod init;
+pub mod kernel;
+pub mod load;
+pub mod logos;
+pub mod memory;
+pub mod modules;
+pub mod network;
+pub mod os_release;
+pub mod packages;
+pub mod pci_ids;
+pub mod swap;
+pub mod sysroot;
+pub mod trace;
+mod uname;
+pub mod virt;
 
-pub fn get_os_name() -> Result<String, Box<dyn Error>> {
-    // Get the name of the Distribution, using the `lsb_release` command.
-    let os_name = Command::new("lsb_release").arg("-sd").output();
-
-    let os_name = match os_name {
-        Ok(x) => String::from_utf8(x.stdout).unwrap(),
-        Err(_) => {
-            // Read the `/etc/os-release` file if `lsb_release` does not exist.
-            let file_name = String::from("/etc/os-release");
-
-            // Read the file
-            let file_contents = fs::read_to_string(file_name)?;
-
-            // Search for `PRETTY_NAME`
-            let search_string = "PRETTY_NAME";
-
-            for line in file_contents.lines() {
-                if line.contains(search_string) {
-                    // Get the value for the key, `PRETTY_NAME`
-                    let vec_new = line.split('=').last().unwrap();
-                    // Remove the '"' , i.e. double quotes from the output.
-                    let vec_new = vec_new.replace('"', "");
-                    return Ok(vec_new);
-                }
-            }
-            String::from("Unknown")
+#[cfg(test)]
+mod testutil;
+
+pub use battery::{Battery, BatteryInfo};
+pub use cache::{Cache, CachePolicy};
+pub use command::{CannedRunner, CommandOutput, CommandRunner, SystemRunner};
+pub use cpu::CpuInfo;
+pub use disk::Disk;
+pub use error::FetchError;
+pub use gpu::Gpu;
+pub use host::HostModel;
+pub use init::InitSystem;
+pub use kernel::{KernelFormat, KernelInfo};
+pub use load::LoadAverage;
+pub use memory::{MemoryInfo, SizeUnit};
+pub use modules::{Diagnosis, InfoModule, Registry};
+pub use network::{InterfaceAddress, InterfaceKind, NetworkInterface};
+pub use os_release::OsRelease;
+pub use swap::{SwapDevice, SwapInfo, SwapKind, ZramStats};
+pub use sysroot::SysRoot;
+pub use trace::{Attempt, Trace};
+pub use virt::{Hypervisor, Virtualization};
+
+use error::ResultExt;
+
+/// Options controlling how [`collect`] probes the system.
+#[derive(Clone)]
+pub struct Options {
+    runner: Arc<dyn CommandRunner>,
+    root: SysRoot,
+    timeout: Duration,
+    timeouts: HashMap<String, Duration>,
+    cache: Option<Cache>,
+    trace: Option<Trace>,
+    kernel_format: KernelFormat,
+    memory_unit: SizeUnit,
+    memory_percent: bool,
+    disks: Vec<String>,
+    disk_overlay: bool,
+    load_per_cpu: bool,
+}
+
+impl Options {
+    /// Execute external commands through `runner`, instead of
+    /// running them on the real system.
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Describe the system under `root`, instead of the running one.
+    pub fn with_root(mut self, root: SysRoot) -> Self {
+        self.root = root;
+        self
+    }
+
+    /// Give up on every probe, that takes longer than `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Give up on the probe for the field `name`, e.g. "packages", once
+    /// it takes longer than `timeout`, instead of the common timeout.
+    pub fn with_field_timeout(mut self, name: &str, timeout: Duration) -> Self {
+        self.timeouts.insert(name.to_string(), timeout);
+        self
+    }
+
+    /// Serve the values of slow modules from `cache`, while they are
+    /// still valid, and store them there once collected.
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Record every source, that the probes try, in `trace`.
+    pub fn with_trace(mut self, trace: Trace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Display the kernel in `format`, e.g. with its build string.
+    pub fn with_kernel_format(mut self, format: KernelFormat) -> Self {
+        self.kernel_format = format;
+        self
+    }
+
+    /// Display the memory, and swap, in `unit`, e.g. GiB.
+    pub fn with_memory_unit(mut self, unit: SizeUnit) -> Self {
+        self.memory_unit = unit;
+        self
+    }
+
+    /// Display the share of the memory, and swap, in use, along with the usage.
+    pub fn with_memory_percent(mut self, percent: bool) -> Self {
+        self.memory_percent = percent;
+        self
+    }
+
+    /// Display the disk usage of the filesystems mounted on `mount_points`,
+    /// e.g. "/" and "/home", instead of only the root filesystem.
+    pub fn with_disks(mut self, mount_points: Vec<String>) -> Self {
+        self.disks = mount_points;
+        self
+    }
+
+    /// Also display overlay filesystems, e.g. the root of a container,
+    /// among the disks.
+    pub fn with_disk_overlay(mut self, overlay: bool) -> Self {
+        self.disk_overlay = overlay;
+        self
+    }
+
+    /// Divide the load averages by the number of online CPUs, so that 1.00
+    /// means, that every CPU is busy.
+    pub fn with_load_per_cpu(mut self, per_cpu: bool) -> Self {
+        self.load_per_cpu = per_cpu;
+        self
+    }
+
+    /// The runner, through which every external command is executed.
+    pub fn runner(&self) -> &dyn CommandRunner {
+        self.runner.as_ref()
+    }
+
+    /// The root of the system being described.
+    pub fn root(&self) -> &SysRoot {
+        &self.root
+    }
+
+    /// The cache for the values of slow modules, if any.
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    /// Try a single `source` of a probe, e.g. "/etc/os-release", recording
+    /// its outcome, if these options have a trace.
+    pub fn attempt<T, F>(&self, source: &str, source_fn: F) -> Result<T, FetchError>
+    where
+        T: fmt::Debug,
+        F: FnOnce() -> Result<T, FetchError>,
+    {
+        match &self.trace {
+            Some(trace) => trace.attempt(source, source_fn),
+            None => source_fn(),
         }
-    };
-    // Remove the '"' , i.e. double quotes from the output.
-    let os_name = os_name.replace('"', "");
-    let os_name = os_name.replace('\n', ""); // Remove any newline character
+    }
+
+    /// How the kernel module displays the kernel.
+    pub fn kernel_format(&self) -> KernelFormat {
+        self.kernel_format
+    }
+
+    /// The unit, the memory modules display their sizes in.
+    pub fn memory_unit(&self) -> SizeUnit {
+        self.memory_unit
+    }
+
+    /// Whether the memory modules display the share in use.
+    pub fn memory_percent(&self) -> bool {
+        self.memory_percent
+    }
+
+    /// The mount points, the disk module displays the usage of.
+    pub fn disks(&self) -> &[String] {
+        &self.disks
+    }
+
+    /// Whether the disk module includes overlay filesystems.
+    pub fn disk_overlay(&self) -> bool {
+        self.disk_overlay
+    }
+
+    /// Whether the load module divides the load averages by the CPUs.
+    pub fn load_per_cpu(&self) -> bool {
+        self.load_per_cpu
+    }
 
-    Ok(os_name)
+    /// How long the probe for the field `name` is given to finish.
+    pub fn timeout_for(&self, name: &str) -> Duration {
+        self.timeouts.get(name).copied().unwrap_or(self.timeout)
+    }
 }
 
-pub fn get_kernel_version() -> String {
-    // Get the kernel version with `uname -r`.
-    let kernel_ver = Command::new("uname").arg("-r").output();
-
-    let kernel_ver = match kernel_ver {
-        Ok(x) => {
-            // Reverse the string obtained from the output.
-            let rev_kernel_ver: String =
-                String::from_utf8(x.stdout).unwrap().chars().rev().collect();
-
-            // Split the string based on `-`, and then reverse it again,
-            // to obtain only the kernel version, and not any other info.
-            let rev_kernel_ver = rev_kernel_ver
-                .split('-')
-                .last()
-                .unwrap()
-                .chars()
-                .rev()
-                .collect();
-
-            rev_kernel_ver
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            runner: Arc::new(SystemRunner::default()),
+            root: SysRoot::host(),
+            timeout: Duration::from_secs(1),
+            timeouts: HashMap::new(),
+            cache: None,
+            trace: None,
+            kernel_format: KernelFormat::default(),
+            memory_unit: SizeUnit::default(),
+            memory_percent: false,
+            disks: vec!["/".to_string()],
+            disk_overlay: false,
+            load_per_cpu: false,
         }
-        Err(_) => "Unknown".to_string(), // If the command fails assign
-                                         // kernel_ver to "Unknown".
-    };
+    }
+}
+
+impl fmt::Debug for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Options")
+            .field("root", &self.root)
+            .field("timeout", &self.timeout)
+            .field("timeouts", &self.timeouts)
+            .field("cache", &self.cache)
+            .field("kernel_format", &self.kernel_format)
+            .field("memory_unit", &self.memory_unit)
+            .field("memory_percent", &self.memory_percent)
+            .field("disks", &self.disks)
+            .field("disk_overlay", &self.disk_overlay)
+            .field("load_per_cpu", &self.load_per_cpu)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Everything `fetchit` knows about the system, as typed values.
+///
+/// A field is an error when the corresponding probe could not determine
+/// it, or did not finish in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemInfo {
+    /// Pretty name of the distribution, e.g. "Arch Linux".
+    pub os_name: Result<String, FetchError>,
+    /// Identity of the distribution, from its os-release file.
+    pub os_release: Result<OsRelease, FetchError>,
+    /// Version of the running kernel, e.g. "5.19.3".
+    pub kernel_version: Result<String, FetchError>,
+    /// Release, flavor, architecture and build of the running kernel.
+    pub kernel: Result<KernelInfo, FetchError>,
+    /// Name of the user's login shell, e.g. "zsh".
+    pub shell: Result<String, FetchError>,
+    /// Name of the Desktop Environment or Window Manager.
+    pub session: Result<String, FetchError>,
+    /// Time elapsed since the system booted.
+    pub uptime: Result<Duration, FetchError>,
+    /// Number of packages installed through the system package manager.
+    pub packages: Result<u32, FetchError>,
+    /// Network hostname of the machine.
+    pub hostname: Result<String, FetchError>,
+    /// Init system of the machine, e.g. systemd.
+    pub init: Result<InitSystem, FetchError>,
+    /// Hypervisor and container, the system runs in.
+    pub virt: Result<Virtualization, FetchError>,
+    /// Hardware model of the machine.
+    pub host: Result<HostModel, FetchError>,
+    /// Model, topology and frequency of the CPU.
+    pub cpu: Result<CpuInfo, FetchError>,
+    /// Memory usage, or the usage of the cgroup in a container.
+    pub memory: Result<MemoryInfo, FetchError>,
+    /// Swap areas and their usage.
+    pub swap: Result<SwapInfo, FetchError>,
+    /// Display controllers on the PCI bus.
+    pub gpus: Result<Vec<Gpu>, FetchError>,
+    /// Usage of the filesystems on the mount points from the options.
+    pub disks: Result<Vec<Disk>, FetchError>,
+    /// Batteries, and whether the AC adapter is connected.
+    pub battery: Result<BatteryInfo, FetchError>,
+    /// Active network interfaces, with the one of the default route first.
+    pub network: Result<Vec<NetworkInterface>, FetchError>,
+    /// Load averages, and the number of tasks.
+    pub load: Result<LoadAverage, FetchError>,
+}
+
+/// Probe the system and gather all the information `fetchit` displays.
+///
+/// The probes run concurrently, and each one is given the timeout set
+/// in `opts` for its field, to finish.
+pub fn collect(opts: &Options) -> SystemInfo {
+    // Start every probe at once, so that the slow ones, e.g. counting
+    // the packages, do not hold up the others.
+    let os_name = spawn_probe(opts, "os", get_os_name);
+    let os_release = spawn_probe(opts, "os", os_release::read);
+    let kernel_version = spawn_probe(opts, "kernel", get_kernel_version);
+    let kernel = spawn_probe(opts, "kernel", kernel::read);
+    let shell = spawn_probe(opts, "shell", get_shell_name);
+    let session = spawn_probe(opts, "session", get_session_name);
+    let uptime = spawn_probe(opts, "uptime", get_sys_uptime);
+    let packages = spawn_probe(opts, "packages", packages::get_num_packages);
+    let hostname = spawn_probe(opts, "hostname", get_hostname);
+    let init = spawn_probe(opts, "init", init::read);
+    let virt = spawn_probe(opts, "virt", virt::read);
+    let host = spawn_probe(opts, "host", host::read);
+    let cpu = spawn_probe(opts, "cpu", cpu::read);
+    let memory = spawn_probe(opts, "memory", memory::read);
+    let swap = spawn_probe(opts, "swap", swap::read);
+    let gpus = spawn_probe(opts, "gpu", gpu::read);
+    let disks = spawn_probe(opts, "disk", disk::read);
+    let battery = spawn_probe(opts, "battery", battery::read);
+    let network = spawn_probe(opts, "network", network::read);
+    let load = spawn_probe(opts, "load", load::read);
+
+    SystemInfo {
+        os_name: os_name.wait(),
+        os_release: os_release.wait(),
+        kernel_version: kernel_version.wait(),
+        kernel: kernel.wait(),
+        shell: shell.wait(),
+        session: session.wait(),
+        uptime: uptime.wait(),
+        packages: packages.wait(),
+        hostname: hostname.wait(),
+        init: init.wait(),
+        virt: virt.wait(),
+        host: host.wait(),
+        cpu: cpu.wait(),
+        memory: memory.wait(),
+        swap: swap.wait(),
+        gpus: gpus.wait(),
+        disks: disks.wait(),
+        battery: battery.wait(),
+        network: network.wait(),
+        load: load.wait(),
+    }
+}
 
-    kernel_ver
+/// Start running `probe` in the background, with the timeout for the
+/// field `name`.
+fn spawn_probe<T: Send + 'static>(
+    opts: &Options,
+    name: &'static str,
+    probe: fn(&Options) -> Result<T, FetchError>,
+) -> deadline::Pending<T> {
+    let timeout = opts.timeout_for(name);
+    let opts = opts.clone();
+    deadline::spawn(name, timeout, move || probe(&opts))
 }
 
-pub fn get_shell_name() -> String {
+pub fn get_os_name(opts: &Options) -> Result<String, FetchError> {
+    // Get the name of the Distribution, using the `lsb_release` command.
+    // If that fails, or prints nothing, read the os-release file instead.
+    host_command(opts, "lsb_release", &["-sd"])
+        .and_then(|os_name| parse_lsb_release(&os_name))
+        .or_try(|| {
+            let os_release = os_release::read(opts)?;
+            os_release
+                .pretty_name
+                .or(os_release.name)
+                .ok_or_else(|| FetchError::ParseError("os-release has no PRETTY_NAME".to_string()))
+        })
+}
+
+/// Parse the output of `lsb_release -sd`, e.g. "\"Debian GNU/Linux 12
+/// (bookworm)\"", which is empty on some distributions.
+fn parse_lsb_release(os_name: &str) -> Result<String, FetchError> {
+    // Remove the '"' , i.e. double quotes, and any newline character.
+    let os_name = os_name.trim().trim_matches('"');
+    if os_name.is_empty() {
+        Err(FetchError::NotFound(
+            "lsb_release printed nothing".to_string(),
+        ))
+    } else {
+        Ok(os_name.to_string())
+    }
+}
+
+pub fn get_kernel_version(opts: &Options) -> Result<String, FetchError> {
+    // Keep only the numeric kernel version, and not any other info.
+    kernel::read(opts).map(|kernel| kernel.version)
+}
+
+pub fn get_shell_name(opts: &Options) -> Result<String, FetchError> {
     // Read the value of the Environment Variable, `SHELL`
-    // to obtain the current shell name.
+    // to obtain the current shell name, and keep only the
+    // last component of the path.
     let shell_var = "SHELL";
-    match env::var(shell_var) {
-        Ok(mut val) => {
-            val = val.replace('/', " "); // Replace all the forward slashes
-                                         // with a space.
-            val.split(' ').last().unwrap().to_string() // Split the string
-                                                       // based on the spaces
-                                                       // and get the last word.
+    let val = env_traced(opts, shell_var)?;
+    val.split('/')
+        .next_back()
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| FetchError::ParseError(format!("invalid ${} {:?}", shell_var, val)))
+}
+
+pub fn get_session_name(opts: &Options) -> Result<String, FetchError> {
+    // Check `DESKTOP_SESSION`, `XDG_SESSION_DESKTOP` and `XDG_CURRENT_DESKTOP`
+    // in that order, to obtain the name of the DE(Desktop Environment)
+    // or WM(Window Manager). Variables that are unset or empty are skipped.
+    for session_name in [
+        "DESKTOP_SESSION",
+        "XDG_SESSION_DESKTOP",
+        "XDG_CURRENT_DESKTOP",
+    ] {
+        if let Ok(wm_name) = env_traced(opts, session_name) {
+            return Ok(wm_name);
         }
-        Err(_) => "Unknown".to_string(), // If the Environment variable is
-                                         // not read, return "Unknown".
     }
+
+    // If none of the Environment variables are set, fall back
+    // to reading `_NET_WM_NAME` using `xprop`.
+    // Without an X display, there is nothing for it to ask.
+    if env::var_os("DISPLAY").is_none_or(|display| display.is_empty()) {
+        return Err(FetchError::NotFound(
+            "no session variables, and $DISPLAY is not set".to_string(),
+        ));
+    }
+
+    let xprop_id = host_command(
+        opts,
+        "xprop",
+        &["-root", "-notype", "_NET_SUPPORTING_WM_CHECK"],
+    )?;
+
+    // Extract the ID
+    let xprop_id = xprop_id.trim().split(' ').next_back().unwrap_or_default();
+
+    // Call `xprop` again, but now by passing in the ID, we just found.
+    let wm_props = host_command(opts, "xprop", &["-id", xprop_id, "-notype"])?;
+
+    // Now, from the output, of the above call, we look for `_NET_WM_NAME`.
+    wm_props
+        .lines()
+        .find(|line| line.contains("_NET_WM_NAME"))
+        .and_then(|line| line.split('=').next_back())
+        // Remove double-quotes and the space literal, which is present
+        // between the `_NET_WM_NAME` and it's value, after the `=` sign.
+        .map(|wm_name| wm_name.replace(['"', ' '], ""))
+        .ok_or_else(|| FetchError::ParseError("no _NET_WM_NAME in `xprop` output".to_string()))
+}
+
+pub fn get_sys_uptime(opts: &Options) -> Result<Duration, FetchError> {
+    // Read the uptime from `/proc/uptime`, and if that fails,
+    // fall back to the `uptime -p` command.
+    opts.attempt("/proc/uptime", || {
+        parse_proc_uptime(&opts.root().read_to_string("/proc/uptime")?)
+    })
+    .or_try(|| parse_uptime_pretty(&host_command(opts, "uptime", &["-p"])?))
+}
+
+/// Parse `/proc/uptime`, i.e. the seconds since boot, followed by
+/// the seconds spent idle, e.g. "3572.53 13958.56".
+pub fn parse_proc_uptime(up_time: &str) -> Result<Duration, FetchError> {
+    up_time
+        .split_whitespace()
+        .next()
+        .and_then(|secs| secs.parse().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| FetchError::ParseError(format!("invalid /proc/uptime {:?}", up_time)))
+}
+
+/// Parse the output of `uptime -p`, e.g. "up 1 day, 2 hours, 5 minutes".
+pub fn parse_uptime_pretty(up_time: &str) -> Result<Duration, FetchError> {
+    let invalid = || FetchError::ParseError(format!("invalid `uptime -p` output {:?}", up_time));
+    let parts = up_time.trim().strip_prefix("up ").ok_or_else(invalid)?;
+
+    let mut secs = 0;
+    for part in parts.split(',') {
+        // Every part is a number, followed by its unit.
+        let mut words = part.split_whitespace();
+        let count: u64 = words
+            .next()
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(invalid)?;
+        let unit = match words.next().map(|unit| unit.trim_end_matches('s')) {
+            Some("year") => 365 * 24 * 60 * 60,
+            Some("week") => 7 * 24 * 60 * 60,
+            Some("day") => 24 * 60 * 60,
+            Some("hour") => 60 * 60,
+            Some("minute") => 60,
+            _ => return Err(invalid()),
+        };
+        secs += count * unit;
+    }
+
+    Ok(Duration::from_secs(secs))
 }
 
-pub fn get_session_name() -> String {
-    // First check `DESKTOP_SESSION`.
-    // Read the value of the Environment Variable, `DESKTOP_SESSION`
-    // to obtain the name of the DE(Desktop Environment) or WM(Window Manager).
-    let session_name = "DESKTOP_SESSION";
-
-    // If that fails, assign `wm_name` to `Unknown`
-    let wm_name = env::var(session_name).unwrap_or_else(|_| "Unknown".to_string());
-
-    // If there was an error, in reading `DESKTOP_SESSION`, or if it is empty,
-    // try reading `XDG_SESSION_DESKTOP`.
-    if wm_name.is_empty() || wm_name == *"Unknown" {
-        let session_name = "XDG_SESSION_DESKTOP";
-        let wm_name = env::var(session_name).unwrap_or_else(|_| "Unknown".to_string());
-
-        // If there was an error, in reading `XDG_SESSION_DESKTOP`, or if it is
-        // empty, try reading `XDG_CURRENT_DESKTOP`.
-        if wm_name.is_empty() || wm_name == *"Unknown" {
-            let session_name = "XDG_CURRENT_DESKTOP";
-            let wm_name = env::var(session_name).unwrap_or_else(|_| "Unknown".to_string());
-
-            // If there was an error, in reading `XDG_CURRENT_DESKTOP`, fall back
-            // to reading `_NET_WM_NAME` using `xprop`.
-            if wm_name.is_empty() || wm_name == *"Unknown" {
-                // Now, we try looking at `_NET_WM_NAME`, by using `xprop`.
-                let xprop_id = Command::new("xprop")
-                    .args(["-root", "-notype", "_NET_SUPPORTING_WM_CHECK"])
-                    .output();
-
-                // If the above command ran successfully, assign its output to `xprop_id`.
-                let xprop_id = match xprop_id {
-                    Ok(x) => String::from_utf8(x.stdout).unwrap(),
-                    Err(_) => "Unknown".to_string(),
-                };
-
-                // Extract the ID
-                let xprop_id = xprop_id.split(' ').last().unwrap();
-
-                // Call `xprop` again, but now by passing in the ID, we just found.
-                let mut wm_name = match Command::new("xprop")
-                    .args(["-id", xprop_id, "-notype"])
-                    .output()
-                {
-                    Ok(x) => String::from_utf8(x.stdout).unwrap(),
-                    Err(_) => "Unknown".to_string(),
-                };
-
-                // Now, from the output, of the above call, we look for `_NET_WM_NAME`.
-                for line in wm_name.lines() {
-                    if line.contains("_NET_WM_NAME") {
-                        wm_name = line
-                            .split('=')
-                            .last()
-                            .unwrap()
-                            .to_string()
-                            .replace('"', "") // Remove double-quotes.
-                            .replace(' ', ""); // Remove space literal, which is
-                                               // present between the `_NET_WM_NAME`
-                                               // and it's value, after the `=` sign.
-                        return wm_name;
-                    }
-                }
-                // If all else fails, return "Unknown".
-                String::from("Unknown")
+/// Format an uptime the way `fetchit` displays it, e.g. "1 d, 2 h, 5 m".
+pub fn format_uptime(up_time: Duration) -> String {
+    let mins = up_time.as_secs() / 60;
+    let (days, hours, mins) = (mins / (24 * 60), mins / 60 % 24, mins % 60);
+
+    let mut parts = Vec::new();
+    if days > 0 {
+        parts.push(format!("{} d", days));
+    }
+    if hours > 0 {
+        parts.push(format!("{} h", hours));
+    }
+    if mins > 0 || parts.is_empty() {
+        parts.push(format!("{} m", mins));
+    }
+    parts.join(", ")
+}
+
+pub fn get_hostname(opts: &Options) -> Result<String, FetchError> {
+    // Read the hostname from `/proc/sys/kernel/hostname`, or `/etc/hostname`
+    // for a system that is not running. As a last resort, use the 'hostname'
+    // command, and if that fails, try `uname -n`.
+    let read = |path| {
+        read_traced(opts, path).and_then(|hostname| {
+            // Remove any new line character
+            let hostname = hostname.trim();
+            if hostname.is_empty() {
+                Err(FetchError::NotFound(format!("{} is empty", path)))
             } else {
-                wm_name
+                Ok(hostname.to_string())
             }
-        } else {
-            wm_name
-        }
-    } else {
-        wm_name
-    }
+        })
+    };
+
+    read("/proc/sys/kernel/hostname")
+        .or_try(|| read("/etc/hostname"))
+        .or_try(|| host_command(opts, "hostname", &[]))
+        .or_try(|| host_command(opts, "uname", &["-n"]))
+        .map(|hostname| hostname.trim().to_string())
 }
 
-pub fn get_sys_uptime() -> String {
-    // Get the uptime using the `uptime -p` command.
-    let up_time = Command::new("uptime").arg("-p").output();
-
-    let up_time = match up_time {
-        Ok(x) => {
-            // Remove the word up.
-            String::from_utf8(x.stdout)
-                .unwrap()
-                .replace("hours", "h") // Replace words with letters.
-                .replace("hour", "h")
-                .replace("minutes", "m")
-                .replace("minute", "m")
-                .replace("days", "d")
-                .replace("day", "d")
-                .replace("up ", "")
+/// Run a command, that describes the running system, and return its
+/// output. For any other root, the command is not run at all.
+fn host_command(opts: &Options, program: &str, args: &[&str]) -> Result<String, FetchError> {
+    opts.attempt(&command::command_line(program, args), || {
+        if opts.root().is_host() {
+            opts.runner().stdout(program, args)
+        } else {
+            Err(FetchError::NotFound(format!(
+                "`{}` only describes the running system",
+                program
+            )))
         }
-        Err(_) => "Unknown".to_string(), // If the command fails, assign
-                                         // up_time to "Unknown".
-    };
+    })
+}
 
-    // Remove any newline character.
+/// Read the file at the absolute system path `path`, under the root
+/// from `opts`, as an attempt of the probe.
+fn read_traced(opts: &Options, path: &str) -> Result<String, FetchError> {
+    opts.attempt(path, || opts.root().read_to_string(path))
+}
 
-    up_time.replace('\n', "")
+/// Read the Environment Variable `name`, as an attempt of the probe,
+/// treating an empty value, the same as an unset one.
+fn env_traced(opts: &Options, name: &str) -> Result<String, FetchError> {
+    opts.attempt(&format!("${}", name), || match env::var(name) {
+        Ok(val) if !val.is_empty() => Ok(val),
+        _ => Err(FetchError::NotFound(format!("${} is not set", name))),
+    })
 }
 
-pub fn get_hostname() -> String {
-    // Get the hostname using the 'hostname' command
-    let hostname = Command::new("hostname").output();
-    let hostname = match hostname {
-        Ok(x) => String::from_utf8(x.stdout).unwrap(),
-        Err(_) => {
-            let hostname = Command::new("uname").arg("-n").output();
-            match hostname {
-                Ok(x) => String::from_utf8(x.stdout).unwrap(),
-                Err(_) => "Unknown".to_string(),
+/// Undo the octal escapes of paths in `/proc/swaps`, or `/proc/self/mountinfo`,
+/// e.g. "\040" for a space.
+fn unescape_octal(name: &str) -> String {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(index) = rest.find('\\') {
+        unescaped.push_str(&rest[..index]);
+        rest = &rest[index..];
+        match rest
+            .get(1..4)
+            .and_then(|octal| u8::from_str_radix(octal, 8).ok())
+        {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[1..];
             }
         }
-    };
-    // Remove any new line character
-    hostname.replace('\n', "")
+    }
+    unescaped + rest
 }
 
 // Add some tests, for testing the `get_session_name()` function.
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testutil::Fixture;
     use std::env;
 
+    // Options, that answer every command from `runner`.
+    fn canned(runner: CannedRunner) -> Options {
+        Options::default().with_runner(Arc::new(runner))
+    }
+
     // #[test]
     // fn fallback_session() {
     //     // Remove all the checked Environment variables.
@@ -245,8 +647,8 @@ mod tests {
         let env_var = "XDG_CURRENT_DESKTOP";
         env::set_var(env_var, "Qtile");
 
-        let wm_name = get_session_name();
-        assert_eq!(wm_name, "Qtile");
+        let wm_name = get_session_name(&canned(CannedRunner::new()));
+        assert_eq!(wm_name.as_deref(), Ok("Qtile"));
     }
 
     #[test]
@@ -265,7 +667,92 @@ mod tests {
         let env_var = "XDG_SESSION_DESKTOP";
         env::set_var(env_var, "Testing");
 
-        let wm_name = get_session_name();
-        assert_eq!(wm_name, "Testing");
+        let wm_name = get_session_name(&canned(CannedRunner::new()));
+        assert_eq!(wm_name.as_deref(), Ok("Testing"));
+    }
+
+    #[test]
+    fn uptime_pretty() {
+        let up_time = parse_uptime_pretty("up 1 week, 2 days, 1 hour, 5 minutes\n");
+        assert_eq!(
+            up_time,
+            Ok(Duration::from_secs(((9 * 24 + 1) * 60 + 5) * 60))
+        );
+        assert_eq!(format_uptime(up_time.unwrap()), "9 d, 1 h, 5 m");
+        assert_eq!(format_uptime(Duration::from_secs(30)), "0 m");
+    }
+
+    #[test]
+    fn native_probes() {
+        let fixture = Fixture::new()
+            .file("/proc/sys/kernel/osrelease", "6.6.8-zen1-1-zen\n")
+            .file("/proc/sys/kernel/hostname", "buildbox\n")
+            .file("/proc/uptime", "7265.41 28110.09\n");
+        let opts = Options::default().with_root(fixture.root());
+
+        assert_eq!(get_kernel_version(&opts).as_deref(), Ok("6.6.8"));
+        assert_eq!(get_hostname(&opts).as_deref(), Ok("buildbox"));
+        assert_eq!(
+            get_sys_uptime(&opts).map(format_uptime).as_deref(),
+            Ok("2 h, 1 m")
+        );
+    }
+
+    #[test]
+    fn commands_only_for_host() {
+        // Commands describe the running system, so they are never run
+        // for any other root.
+        let runner = CannedRunner::new()
+            .with("uname -r", 0, "6.1.0-13-amd64\n")
+            .with("uptime -p", 0, "up 2 hours, 1 minute\n")
+            .with("hostname", 0, "host\n");
+        let fixture = Fixture::new().file("/etc/hostname", "image\n");
+        let opts = canned(runner).with_root(fixture.root());
+
+        assert!(get_kernel_version(&opts).unwrap_err().is_not_found());
+        assert!(get_sys_uptime(&opts).unwrap_err().is_not_found());
+        assert_eq!(get_hostname(&opts).as_deref(), Ok("image"));
+    }
+
+    #[test]
+    fn os_name_from_root() {
+        let fixture = Fixture::new().file(
+            "/etc/os-release",
+            "NAME=\"Arch Linux\"\nPRETTY_NAME=\"Arch Linux\"\nID=arch\n",
+        );
+        // `lsb_release` describes the running system, so it must not be used.
+        let runner = CannedRunner::new().with("lsb_release -sd", 0, "Debian GNU/Linux 12\n");
+        let opts = canned(runner).with_root(fixture.root());
+
+        assert_eq!(get_os_name(&opts).unwrap(), "Arch Linux");
+        assert!(get_os_name(&Options::default().with_root(Fixture::new().root())).is_err());
+    }
+
+    #[test]
+    fn empty_lsb_release_is_ignored() {
+        assert_eq!(
+            parse_lsb_release("\"Debian GNU/Linux 12 (bookworm)\"\n").as_deref(),
+            Ok("Debian GNU/Linux 12 (bookworm)")
+        );
+        assert!(parse_lsb_release("\n").unwrap_err().is_not_found());
+        assert!(parse_lsb_release("\"\"\n").unwrap_err().is_not_found());
+
+        // `lsb_release` runs on the host, but prints nothing useful, so the
+        // os-release file of the host names the distribution.
+        let host = os_release::read(&Options::default()).unwrap();
+        let expected = host.pretty_name.or(host.name);
+        for empty in ["", "\"\"\n"] {
+            let runner = CannedRunner::new().with("lsb_release -sd", 0, empty);
+            assert_eq!(get_os_name(&canned(runner)).ok(), expected);
+        }
+
+        // For another root, `lsb_release` is not run at all, and the
+        // os-release file under the root names the distribution.
+        let fixture = Fixture::new().file(
+            "/etc/os-release",
+            "NAME=\"Alpine Linux\"\nPRETTY_NAME=\"Alpine Linux v3.19\"\n",
+        );
+        let opts = Options::default().with_root(fixture.root());
+        assert_eq!(get_os_name(&opts).as_deref(), Ok("Alpine Linux v3.19"));
     }
 }
@@ -0,0 +1,147 @@
+// @Brief : This file contains the load averages of the system, and the
+//          number of tasks, from `/proc/loadavg`.
+
+use crate::error::ResultExt;
+use crate::{FetchError, Options};
+use std::fmt;
+
+/// The load averages, and the tasks, e.g. of a shared build machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadAverage {
+    /// The load averages over 1, 5 and 15 minutes, in hundredths, exactly
+    /// as precise as the kernel reports them, e.g. 52 for "0.52".
+    pub hundredths: [u32; 3],
+    /// Tasks, that are running, or waiting to run, right now.
+    pub running: u32,
+    /// Tasks, i.e. processes and threads, that exist.
+    pub total: u32,
+    /// Number of online CPUs, if known, which the load saturates at.
+    pub cpus: Option<u32>,
+}
+
+impl LoadAverage {
+    /// The load averages over 1, 5 and 15 minutes, e.g. 0.52.
+    pub fn averages(&self) -> [f64; 3] {
+        self.hundredths.map(|load| f64::from(load) / 100.0)
+    }
+
+    /// Render the load averages, e.g. "0.52, 0.58, 0.59 (2/345 tasks)".
+    /// If `per_cpu` is set, and the number of CPUs is known, they are
+    /// divided by it, so 1.00 means, that every CPU is busy.
+    pub fn display(&self, per_cpu: bool) -> String {
+        let (divisor, suffix) = match self.cpus {
+            Some(cpus) if per_cpu && cpus > 0 => (f64::from(cpus), " per CPU"),
+            _ => (1.0, ""),
+        };
+        let averages: Vec<String> = self
+            .averages()
+            .iter()
+            .map(|load| format!("{:.2}", load / divisor))
+            .collect();
+        format!(
+            "{}{} ({}/{} tasks)",
+            averages.join(", "),
+            suffix,
+            self.running,
+            self.total
+        )
+    }
+}
+
+impl fmt::Display for LoadAverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display(false))
+    }
+}
+
+/// Read the load averages from `/proc/loadavg`, and the number of online
+/// CPUs, to divide them by.
+pub fn read(opts: &Options) -> Result<LoadAverage, FetchError> {
+    let loadavg = crate::read_traced(opts, "/proc/loadavg")?;
+    let mut load = parse_loadavg(&loadavg)
+        .ok_or_else(|| FetchError::ParseError(format!("invalid /proc/loadavg {:?}", loadavg)))?;
+    load.cpus = online_cpus(opts);
+    Ok(load)
+}
+
+/// Parse `/proc/loadavg`, e.g. "0.52 0.58 0.59 2/345 12345", where the
+/// last field is the most recent PID.
+fn parse_loadavg(loadavg: &str) -> Option<LoadAverage> {
+    let mut fields = loadavg.split_whitespace();
+    let mut hundredths = [0; 3];
+    for load in &mut hundredths {
+        let (whole, fraction) = fields.next()?.split_once('.')?;
+        *load = whole.parse::<u32>().ok()? * 100 + fraction.get(..2)?.parse::<u32>().ok()?;
+    }
+    let (running, total) = fields.next()?.split_once('/')?;
+    Some(LoadAverage {
+        hundredths,
+        running: running.parse().ok()?,
+        total: total.parse().ok()?,
+        cpus: None,
+    })
+}
+
+/// The number of online CPUs, from the ranges in sysfs, e.g. "0-3,6", or
+/// else the processors in `/proc/cpuinfo`.
+fn online_cpus(opts: &Options) -> Option<u32> {
+    let from_ranges = |online: String| -> Result<u32, FetchError> {
+        let invalid = || FetchError::ParseError(format!("invalid CPU ranges {:?}", online));
+        online
+            .trim()
+            .split(',')
+            .map(|range| {
+                let (first, last) = range.split_once('-').unwrap_or((range, range));
+                let first: u32 = first.parse().map_err(|_| invalid())?;
+                let last: u32 = last.parse().map_err(|_| invalid())?;
+                Ok(last.saturating_sub(first) + 1)
+            })
+            .sum()
+    };
+    crate::read_traced(opts, "/sys/devices/system/cpu/online")
+        .and_then(from_ranges)
+        .or_try(|| {
+            let cpuinfo = crate::read_traced(opts, "/proc/cpuinfo")?;
+            Ok(cpuinfo
+                .lines()
+                .filter(|line| line.starts_with("processor"))
+                .count() as u32)
+        })
+        .ok()
+        .filter(|&cpus| cpus > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    #[test]
+    fn averages_and_cpus() {
+        let fixture = Fixture::new()
+            .file("/proc/loadavg", "6.04 3.50 0.59 9/1345 12345\n")
+            .file("/sys/devices/system/cpu/online", "0-3,6\n");
+        let load = read(&Options::default().with_root(fixture.root())).unwrap();
+        assert_eq!(
+            load,
+            LoadAverage {
+                hundredths: [604, 350, 59],
+                running: 9,
+                total: 1345,
+                cpus: Some(5),
+            }
+        );
+        assert_eq!(load.to_string(), "6.04, 3.50, 0.59 (9/1345 tasks)");
+        assert_eq!(
+            load.display(true),
+            "1.21, 0.70, 0.12 per CPU (9/1345 tasks)"
+        );
+
+        let cpuinfo = Fixture::new()
+            .file("/proc/loadavg", "0.00 0.02 0.06 1/80 4242\n")
+            .file("/proc/cpuinfo", "processor\t: 0\n\nprocessor\t: 1\n");
+        let load = read(&Options::default().with_root(cpuinfo.root())).unwrap();
+        assert_eq!(load.cpus, Some(2));
+        assert_eq!(load.display(true), "0.00, 0.01, 0.03 per CPU (1/80 tasks)");
+    }
+}
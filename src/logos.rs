@@ -0,0 +1,136 @@
+// @Brief : This file contains the ascii logos bundled with `fetchit`,
+//          and the logic to pick the one matching the distribution.
+
+use crate::OsRelease;
+
+/// An ascii logo, available in a large and a small variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Logo {
+    /// Name used to select the logo with `--logo`, e.g. "arch".
+    pub name: &'static str,
+    /// The os-release IDs of the distributions, this logo belongs to.
+    ids: &'static [&'static str],
+    pub large: &'static str,
+    pub small: &'static str,
+}
+
+impl Logo {
+    /// The variant of the logo, either the small or the large one.
+    pub fn art(&self, small: bool) -> &'static str {
+        if small {
+            self.small
+        } else {
+            self.large
+        }
+    }
+}
+
+/// The Rust laptop, displayed for any distribution without a logo.
+pub const DEFAULT: Logo = Logo {
+    name: "laptop",
+    ids: &[],
+    large: include_str!("../ascii_assets/laptop.txt"),
+    small: include_str!("../ascii_assets/laptop_small.txt"),
+};
+
+/// Every bundled logo, the default one included.
+pub const LOGOS: &[Logo] = &[
+    DEFAULT,
+    Logo {
+        name: "alpine",
+        ids: &["alpine"],
+        large: include_str!("../ascii_assets/alpine.txt"),
+        small: include_str!("../ascii_assets/alpine_small.txt"),
+    },
+    Logo {
+        name: "arch",
+        ids: &["arch", "archarm"],
+        large: include_str!("../ascii_assets/arch.txt"),
+        small: include_str!("../ascii_assets/arch_small.txt"),
+    },
+    Logo {
+        name: "debian",
+        ids: &["debian"],
+        large: include_str!("../ascii_assets/debian.txt"),
+        small: include_str!("../ascii_assets/debian_small.txt"),
+    },
+    Logo {
+        name: "fedora",
+        ids: &["fedora"],
+        large: include_str!("../ascii_assets/fedora.txt"),
+        small: include_str!("../ascii_assets/fedora_small.txt"),
+    },
+    Logo {
+        name: "gentoo",
+        ids: &["gentoo"],
+        large: include_str!("../ascii_assets/gentoo.txt"),
+        small: include_str!("../ascii_assets/gentoo_small.txt"),
+    },
+    Logo {
+        name: "nixos",
+        ids: &["nixos"],
+        large: include_str!("../ascii_assets/nixos.txt"),
+        small: include_str!("../ascii_assets/nixos_small.txt"),
+    },
+    Logo {
+        name: "opensuse",
+        ids: &["opensuse", "opensuse-leap", "opensuse-tumbleweed", "suse"],
+        large: include_str!("../ascii_assets/opensuse.txt"),
+        small: include_str!("../ascii_assets/opensuse_small.txt"),
+    },
+    Logo {
+        name: "ubuntu",
+        ids: &["ubuntu"],
+        large: include_str!("../ascii_assets/ubuntu.txt"),
+        small: include_str!("../ascii_assets/ubuntu_small.txt"),
+    },
+    Logo {
+        name: "void",
+        ids: &["void"],
+        large: include_str!("../ascii_assets/void.txt"),
+        small: include_str!("../ascii_assets/void_small.txt"),
+    },
+];
+
+/// Find a logo by its name, or by the os-release ID of its distribution.
+pub fn find(name: &str) -> Option<&'static Logo> {
+    let name = name.to_lowercase();
+    LOGOS
+        .iter()
+        .find(|logo| logo.name == name || logo.ids.contains(&name.as_str()))
+}
+
+/// The logo of the distribution, by its `ID`, or else by the closest
+/// distribution in its `ID_LIKE`, e.g. Debian for Raspberry Pi OS.
+pub fn for_os_release(os_release: &OsRelease) -> Option<&'static Logo> {
+    os_release
+        .id
+        .iter()
+        .chain(&os_release.id_like)
+        .find_map(|id| LOGOS.iter().find(|logo| logo.ids.contains(&id.as_str())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_like_fallback() {
+        let manjaro = OsRelease::parse("ID=manjaro\nID_LIKE=arch\n");
+        assert_eq!(for_os_release(&manjaro).map(|logo| logo.name), Some("arch"));
+
+        let mint = OsRelease::parse("ID=linuxmint\nID_LIKE=\"ubuntu debian\"\n");
+        assert_eq!(for_os_release(&mint).map(|logo| logo.name), Some("ubuntu"));
+
+        let unknown = OsRelease::parse("ID=serenity\n");
+        assert_eq!(for_os_release(&unknown), None);
+
+        assert_eq!(
+            find("openSUSE-Tumbleweed").map(|logo| logo.name),
+            Some("opensuse")
+        );
+        assert!(LOGOS
+            .iter()
+            .all(|logo| !logo.large.is_empty() && !logo.small.is_empty()));
+    }
+}
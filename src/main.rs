@@ -5,223 +5,329 @@
 use clap::Parser;
 use colored::Color;
 use colored::Colorize;
+use fetchit::{logos, os_release, Diagnosis, FetchError, Registry, SystemRunner};
 use std::fs;
-
-// Bring the functions from `lib.rs`, and
-// `packages.rs` into scope.
-
-pub mod packages;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 
 fn main() {
     let args = FetchitArgs::parse();
 
-    let os_name = match fetchit::get_os_name() {
-        Ok(x) => x,
-        Err(_) => String::from("Unknown"),
+    // Pick the modules to display, either the ones passed on the
+    // command line, or all the built-in ones in their default order.
+    let registry = match &args.modules {
+        Some(names) => Registry::from_names(names).unwrap_or_else(|name| {
+            eprintln!(
+                "fetchit: unknown module `{}`, available modules: {}",
+                name,
+                Registry::builtin_names().collect::<Vec<_>>().join(", ")
+            );
+            process::exit(2);
+        }),
+        None => Registry::default(),
     };
 
-    let kernel = fetchit::get_kernel_version();
-
-    let shell_name = fetchit::get_shell_name();
+    let mut opts = fetchit::Options::default();
+    if let Some(root) = args.root {
+        opts = opts.with_root(fetchit::SysRoot::new(root));
+    }
+    if !args.no_cache {
+        if let Some(cache) = fetchit::Cache::default_dir() {
+            opts = opts.with_cache(cache);
+        }
+    }
+    if let Some(format) = args.kernel_format {
+        opts = opts.with_kernel_format(format);
+    }
+    if let Some(unit) = args.memory_unit {
+        opts = opts.with_memory_unit(unit);
+    }
+    opts = opts.with_memory_percent(args.memory_percent);
+    if let Some(disks) = args.disks {
+        opts = opts.with_disks(disks);
+    }
+    opts = opts.with_disk_overlay(args.disk_overlay);
+    opts = opts.with_load_per_cpu(args.load_per_cpu);
+    for (name, timeout) in args.timeout {
+        opts = match name {
+            Some(name) => opts.with_field_timeout(&name, timeout),
+            None => opts.with_timeout(timeout),
+        };
+    }
 
-    let session = fetchit::get_session_name();
+    // Commands are killed once the slowest module is no longer waited for.
+    let command_timeout = registry
+        .iter()
+        .map(|module| opts.timeout_for(module.name()))
+        .max()
+        .unwrap_or_default();
+    let runner = SystemRunner::with_timeout(command_timeout);
+    opts = opts.with_runner(Arc::new(runner.clone()));
 
-    let uptime = fetchit::get_sys_uptime();
+    // Explain where every value came from, instead of displaying them.
+    if args.doctor {
+        print_diagnosis(&registry.diagnose(&opts));
+        runner.kill_all();
+        return;
+    }
 
-    let total_packages = packages::get_num_packages().to_string();
+    // Collect the value of every module. Anything that is absent on this
+    // system is displayed as "Unknown", and anything that went wrong, as
+    // a short description of the error.
+    let values: Vec<String> = registry
+        .collect(&opts)
+        .into_iter()
+        .map(|value| match value {
+            Ok(value) => value,
+            Err(FetchError::NotFound(_)) => String::from("Unknown"),
+            Err(FetchError::Timeout(_)) => String::from("Timed out"),
+            Err(FetchError::PermissionDenied(_)) => String::from("Permission denied"),
+            Err(FetchError::CommandFailed(_) | FetchError::ParseError(_)) => String::from("Error"),
+        })
+        .collect();
 
-    let hostname = fetchit::get_hostname();
-    // Create a vector to store the lengths of all the strings
-    let string_length_vector = vec![
-        os_name.len(),
-        kernel.len(),
-        shell_name.len(),
-        session.len(),
-        uptime.len(),
-        total_packages.len(),
-        hostname.len(),
-    ];
+    // Anything still running belongs to a module, that timed out, so
+    // make sure it does not outlive `fetchit`.
+    runner.kill_all();
 
-    // Initialize the maximum string length to `0.`
-    let mut max_val = 0;
+    // Pick the ascii art, either the logo passed on the command line, or
+    // the one of the distribution, falling back to the Rust laptop.
+    let os_release = os_release::read(&opts).ok();
+    let logo = match &args.logo {
+        Some(name) => logos::find(name).unwrap_or_else(|| {
+            eprintln!(
+                "fetchit: unknown logo `{}`, available logos: {}",
+                name,
+                logos::LOGOS
+                    .iter()
+                    .map(|logo| logo.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            process::exit(2);
+        }),
+        None => os_release
+            .as_ref()
+            .and_then(logos::for_os_release)
+            .unwrap_or(&logos::DEFAULT),
+    };
+    let mut ascii_string = logo.art(args.small_logo).to_string();
 
-    // Iterate over the vector, to find the maximum string length.
-    for val in &string_length_vector {
-        if val > &max_val {
-            max_val = *val;
+    // Update the ascii art if a file was passed, but
+    // a check for the required length is also done.
+    if let Some(file_path) = args.file_path {
+        match fs::read_to_string(&file_path) {
+            Ok(custom_ascii_string) if custom_ascii_string.lines().count() >= 9 => {
+                ascii_string = custom_ascii_string;
+            }
+            Ok(_) => eprintln!(
+                "fetchit: {} has less than 9 lines, using the {} logo",
+                file_path.display(),
+                logo.name
+            ),
+            Err(e) => eprintln!(
+                "fetchit: could not read {}: {}, using the {} logo",
+                file_path.display(),
+                e,
+                logo.name
+            ),
         }
     }
 
-    // Define the length for which the horizontal characters `─`
-    // should be repeated.
-    let final_length = 11 + max_val + 5;
-
-    // Define the default ascii art.
-    let mut ascii_string = "     ______________        
-    |  __________  |       
-    | :          : |       
-    | :   Rust   : |       
-    | :__________: |       
-    |______________|       
-    \\   =========   \\      
-     \\ ==== ____ === \\     
-      \\_____\\___\\_____\\    "
-        .to_string();
-
-    // Check for custom file, if given.
-    let custom_ascii_string = match args.file_path {
-        Some(x) => match fs::read_to_string(x) {
-            Ok(y) => y,
-            Err(_) => "Unknown".to_string(),
-        },
-        None => "Unknown".to_string(),
+    // Without any colors passed on the command line, the art, the box
+    // and the labels are all drawn in the color of the distribution, from
+    // `ANSI_COLOR` in os-release. Otherwise, the colors that were not
+    // passed, keep their usual defaults.
+    let distro_color = os_release
+        .as_ref()
+        .and_then(|os_release| os_release.color());
+    let no_colors_passed =
+        args.top_color.is_none() && args.bottom_color.is_none() && args.outer_box_color.is_none();
+    let label_color = distro_color.filter(|_| no_colors_passed);
+    let color_or = |color: Option<String>, default: Color| {
+        color
+            .and_then(|color| color.parse().ok())
+            .or(label_color)
+            .unwrap_or(default)
     };
+    let top_color = color_or(args.top_color, Color::Red);
+    let bottom_color = color_or(args.bottom_color, Color::Blue);
+    let outer_box_color = color_or(args.outer_box_color, Color::Blue);
 
-    // Update the ascii art if a file was passed, but
-    // a check for the required length is also done.
-    if custom_ascii_string != *"Unknown" {
-        let mut ascii_lines = 0;
-        for _ in custom_ascii_string.lines() {
-            ascii_lines += 1;
-        }
-        if ascii_lines >= 9 {
-            ascii_string = custom_ascii_string;
-        }
+    let ascii_vec = color_ascii_art(&ascii_string, top_color, bottom_color);
+    let box_vec = draw_box(&registry, &values, outer_box_color, label_color);
+
+    // Print the ascii art, and the box side by side. If one of them is
+    // taller than the other, the shorter one is padded with blank lines.
+    let ascii_width = ascii_string.lines().map(display_width).max().unwrap_or(0);
+    let blank_ascii = " ".repeat(ascii_width);
+
+    println!();
+    for i in 0..ascii_vec.len().max(box_vec.len()) {
+        println!(
+            "{} {}",
+            ascii_vec.get(i).unwrap_or(&blank_ascii),
+            box_vec.get(i).map(String::as_str).unwrap_or("")
+        );
     }
+    println!();
+}
+
+/// Pad every line of the ascii art to the same width, and color the top
+/// two thirds of it with `top_color`, and the rest with `bottom_color`.
+fn color_ascii_art(ascii_string: &str, top_color: Color, bottom_color: Color) -> Vec<String> {
+    let ascii_width = ascii_string.lines().map(display_width).max().unwrap_or(0);
+    let ascii_lines = ascii_string.lines().count();
+
+    ascii_string
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let color = if i < ascii_lines * 2 / 3 {
+                top_color
+            } else {
+                bottom_color
+            };
+            let line = format!("{}{}", line, " ".repeat(ascii_width - display_width(line)));
+            line.color(color).to_string()
+        })
+        .collect()
+}
+
+/// Draw the bounding box, with one line for every module in the registry.
+/// The labels and icons are drawn in `label_color`, if it is set, and
+/// otherwise in the color of their module.
+fn draw_box(
+    registry: &Registry,
+    values: &[String],
+    outer_box_color: Color,
+    label_color: Option<Color>,
+) -> Vec<String> {
+    // The labels are padded to a common width, with two spaces after the
+    // longest one, and every icon takes up three columns, i.e. the icon
+    // itself followed by the spaces, needed to line up the values.
+    let label_width = registry
+        .iter()
+        .map(|module| module.label().len())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let value_width = values
+        .iter()
+        .map(|value| display_width(value))
+        .max()
+        .unwrap_or(0);
+
+    // Define the length for which the horizontal characters `─`
+    // should be repeated. The `1` is for the space after the box side,
+    // and the `2` is for the spaces after the longest value.
+    let final_length = 1 + label_width + 3 + value_width + 2;
 
-    let mut ascii_vec: Vec<String> = Vec::new();
+    let box_side = "│".color(outer_box_color).to_string();
+    let box_top = "─".repeat(final_length).color(outer_box_color).to_string();
 
-    for line in ascii_string.lines() {
-        ascii_vec.push(line.to_string());
+    let mut box_vec = vec![format!(
+        "{}{}{}",
+        "╭".color(outer_box_color),
+        box_top,
+        "╮".color(outer_box_color)
+    )];
+
+    for (module, value) in registry.iter().zip(values) {
+        let color = label_color.unwrap_or_else(|| module.color());
+        box_vec.push(
+            format!(
+                "{} {}{}{}{}{}{}",
+                box_side,
+                module.label().color(color).bold().italic(),
+                " ".repeat(label_width - module.label().len()),
+                module.icon().color(color),
+                " ".repeat(3 - display_width(module.icon()).min(3)),
+                value,
+                " ".repeat(value_width + 2 - display_width(value)),
+            ) + &box_side,
+        );
     }
 
-    let top_color = args
-        .top_color
-        .unwrap_or_else(|| "red".into())
-        .parse()
-        .unwrap_or(Color::Red);
-    let bottom_color = args
-        .bottom_color
-        .unwrap_or_else(|| "blue".into())
-        .parse()
-        .unwrap_or(Color::Blue);
-    for (i, item) in ascii_vec.iter_mut().enumerate().take(9) {
-        let color = if i < 6 { top_color } else { bottom_color };
-        *item = item.color(color).to_string();
+    box_vec.push(format!(
+        "{}{}{}",
+        "╰".color(outer_box_color),
+        box_top,
+        "╯".color(outer_box_color)
+    ));
+
+    box_vec
+}
+
+/// Print the outcome of every module, followed by each source it tried,
+/// in the order they were tried, e.g.
+///
+/// ```text
+/// os: Arch Linux (3.1 ms)
+///   ✗ lsb_release -sd: not found: lsb_release: No such file ... (0.2 ms)
+///   ✓ /etc/os-release: "NAME=\"Arch Linux\"\n..." (0.1 ms)
+/// ```
+fn print_diagnosis(diagnosis: &[Diagnosis]) {
+    for module in diagnosis {
+        let result = match &module.result {
+            Ok(value) => value.green().to_string(),
+            Err(err) => err.to_string().red().to_string(),
+        };
+        println!(
+            "{}: {} ({})",
+            module.name.bold(),
+            result,
+            format_elapsed(module.elapsed)
+        );
+
+        if module.attempts.is_empty() {
+            println!("  {}", "no sources were tried".dimmed());
+        }
+        for attempt in &module.attempts {
+            let (mark, outcome) = match &attempt.outcome {
+                Ok(value) => ("✓".green(), value.clone()),
+                Err(err) => ("✗".red(), err.to_string()),
+            };
+            println!(
+                "  {} {}: {} ({})",
+                mark,
+                attempt.source,
+                outcome,
+                format_elapsed(attempt.elapsed)
+            );
+        }
     }
+}
 
-    let mut box_side = "│".to_string();
-    let mut box_top = "─".to_string().repeat(final_length);
-    let mut box_top_left_corner = "╭".to_string();
-    let mut box_top_right_corner = "╮".to_string();
-    let mut box_bottom_left_corner = "╰".to_string();
-    let mut box_bottom_right_corner = "╯".to_string();
+/// Format a duration in milliseconds, e.g. "3.1 ms".
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:.1} ms", elapsed.as_secs_f64() * 1000.0)
+}
 
-    let outer_box_color = args
-        .outer_box_color
-        .unwrap_or_else(|| "blue".into())
+/// Parse a timeout in milliseconds, either for all the modules, e.g. "500",
+/// or for a single one, e.g. "packages=3000".
+fn parse_timeout(timeout: &str) -> Result<(Option<String>, Duration), String> {
+    let (name, millis) = match timeout.split_once('=') {
+        Some((name, millis)) => (Some(name.to_string()), millis),
+        None => (None, timeout),
+    };
+    let millis = millis
         .parse()
-        .unwrap_or(Color::Blue);
-    box_side = box_side.color(outer_box_color).to_string();
-    box_top = box_top.color(outer_box_color).to_string();
-    box_top_left_corner = box_top_left_corner.color(outer_box_color).to_string();
-    box_top_right_corner = box_top_right_corner.color(outer_box_color).to_string();
-    box_bottom_left_corner = box_bottom_left_corner.color(outer_box_color).to_string();
-    box_bottom_right_corner = box_bottom_right_corner.color(outer_box_color).to_string();
-
-    // The number `14` defines the total characters, upto the output
-    // for each system info value. For example,
-    // ` OS          ` this will be printed before the name of the OS is
-    // printed, and this text contains 14 characters. The `11` and `5` above
-    // are chosen based on that formatting.
+        .map_err(|_| format!("`{}` is not a number of milliseconds", millis))?;
+    Ok((name, Duration::from_millis(millis)))
+}
 
-    println!();
-    println!(
-        "{} {}{}{}",
-        ascii_vec[0], box_top_left_corner, box_top, box_top_right_corner
-    );
-    println!(
-        "{} {} {}        {}  {}{}{}",
-        ascii_vec[1],
-        box_side,
-        "OS".to_string().red().bold().italic(),
-        "".to_string().red(),
-        os_name,
-        " ".to_string()
-            .repeat(final_length - 14 - string_length_vector[0]),
-        box_side
-    );
-    println!(
-        "{} {} {}    {}  {}{}{}",
-        ascii_vec[2],
-        box_side,
-        "KERNEL".to_string().magenta().bold().italic(),
-        "".to_string().magenta(),
-        kernel,
-        " ".to_string()
-            .repeat(final_length - 14 - string_length_vector[1]),
-        box_side
-    );
-    println!(
-        "{} {} {}     {}  {}{}{}",
-        ascii_vec[3],
-        box_side,
-        "SHELL".to_string().yellow().bold().italic(),
-        "".to_string().yellow(),
-        shell_name,
-        " ".to_string()
-            .repeat(final_length - 14 - string_length_vector[2]),
-        box_side
-    );
-    println!(
-        "{} {} {}   {}  {}{}{}",
-        ascii_vec[4],
-        box_side,
-        "SESSION".to_string().blue().bold().italic(),
-        "".to_string().blue(),
-        session,
-        " ".to_string()
-            .repeat(final_length - 14 - string_length_vector[3]),
-        box_side
-    );
-    println!(
-        "{} {} {}    {} {}{}{}",
-        ascii_vec[5],
-        box_side,
-        "UPTIME".to_string().cyan().bold().italic(),
-        "祥".to_string().cyan(),
-        uptime,
-        " ".to_string()
-            .repeat(final_length - 14 - string_length_vector[4]),
-        box_side
-    );
-    println!(
-        "{} {} {}  {}  {}{}{}",
-        ascii_vec[6],
-        box_side,
-        "PACKAGES".to_string().green().bold().italic(),
-        "".to_string().green(),
-        total_packages,
-        " ".to_string()
-            .repeat(final_length - 14 - string_length_vector[5]),
-        box_side
-    );
-    println!(
-        "{} {} {}  {}  {}{}{}",
-        ascii_vec[7],
-        box_side,
-        "HOSTNAME".to_string().white().bold().italic(),
-        "".to_string().white(),
-        hostname,
-        " ".to_string()
-            .repeat(final_length - 14 - string_length_vector[6]),
-        box_side
-    );
-    println!(
-        "{} {}{}{}",
-        ascii_vec[8], box_bottom_left_corner, box_top, box_bottom_right_corner
-    );
-    println!();
+/// Number of terminal columns taken up by `text`. Characters from the CJK
+/// ranges, which includes some of the Nerd Font glyphs, take up two columns.
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF => 2,
+            0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 => 2,
+            _ => 1,
+        })
+        .sum()
 }
 
 #[derive(Debug, Parser)]
@@ -245,4 +351,70 @@ struct FetchitArgs {
     /// File path for the ascii text file
     #[clap(short, long, parse(from_os_str))]
     file_path: Option<std::path::PathBuf>,
+
+    /// How much of the kernel to display, i.e. the release, or also the
+    /// architecture and the build : short, full
+    #[clap(long, value_parser)]
+    kernel_format: Option<fetchit::KernelFormat>,
+
+    /// Name of the logo to display, instead of the one of the distribution
+    /// : laptop, alpine, arch, debian, fedora, gentoo, nixos, opensuse, ubuntu, void
+    #[clap(short, long, value_parser)]
+    logo: Option<String>,
+
+    /// Display the small variant of the logo
+    #[clap(short, long)]
+    small_logo: bool,
+
+    /// Unit for the memory and swap sizes, binary or decimal
+    /// : mib, gib, mb, gb
+    #[clap(long, value_parser)]
+    memory_unit: Option<fetchit::SizeUnit>,
+
+    /// Display the share of the memory and swap in use, along with the usage
+    #[clap(long)]
+    memory_percent: bool,
+
+    /// Comma separated list of mount points, whose disk usage to display,
+    /// e.g. /,/home. Defaults to /
+    #[clap(long, value_parser, use_value_delimiter = true)]
+    disks: Option<Vec<String>>,
+
+    /// Also display overlay filesystems, e.g. the root of a container,
+    /// among the disks
+    #[clap(long)]
+    disk_overlay: bool,
+
+    /// Divide the load averages by the number of CPUs, so that 1.00 means,
+    /// that every CPU is busy
+    #[clap(long)]
+    load_per_cpu: bool,
+
+    /// Comma separated list of modules to display, in order
+    /// : os, kernel, shell, session, uptime, packages, hostname, and the ones not
+    /// displayed by default : init, virt, host, cpu, memory, swap, gpu,
+    /// disk, battery, network, load
+    #[clap(short, long, value_parser, use_value_delimiter = true)]
+    modules: Option<Vec<String>>,
+
+    /// Root directory of the system to describe, e.g. a mounted disk
+    /// image or a chroot, instead of the running system
+    #[clap(short, long, parse(from_os_str))]
+    root: Option<std::path::PathBuf>,
+
+    /// Timeout in milliseconds, after which a module is no longer waited
+    /// for, either for all the modules, e.g. 500, or for a single one,
+    /// e.g. packages=3000. Defaults to 1000
+    #[clap(long, value_parser = parse_timeout, use_value_delimiter = true)]
+    timeout: Vec<(Option<String>, Duration)>,
+
+    /// Collect every module again, instead of serving slow ones, e.g. the
+    /// packages count, from the cache in $XDG_CACHE_HOME/fetchit
+    #[clap(long)]
+    no_cache: bool,
+
+    /// Instead of the box, print every source each module tried, with
+    /// its outcome and the time it took, to find out why a value is wrong
+    #[clap(long)]
+    doctor: bool,
 }
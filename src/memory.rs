@@ -0,0 +1,300 @@
+// @Brief : This file contains the memory usage, from `/proc/meminfo`, or
+//          the limit of the cgroup, `fetchit` runs in.
+
+use crate::{FetchError, Options};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// The memory usage, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryInfo {
+    /// Memory in use, i.e. neither free, nor reclaimable cache.
+    pub used: u64,
+    /// Total memory, or the memory limit of the cgroup, if lower.
+    pub total: u64,
+}
+
+impl MemoryInfo {
+    /// The share of the memory in use, in percent.
+    pub fn percent(&self) -> u64 {
+        (self.used * 100).checked_div(self.total).unwrap_or(0)
+    }
+
+    /// Render the usage in `unit`, e.g. "1234 MiB / 7890 MiB", followed by
+    /// the percentage, if `percent` is set, e.g. "(16%)".
+    pub fn display(&self, unit: SizeUnit, percent: bool) -> String {
+        let mut usage = format!("{} / {}", unit.format(self.used), unit.format(self.total));
+        if percent {
+            usage += &format!(" ({}%)", self.percent());
+        }
+        usage
+    }
+}
+
+/// The unit, sizes are displayed in, either binary (IEC), or decimal (SI).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeUnit {
+    /// Mebibytes, i.e. 1024², e.g. "7890 MiB".
+    #[default]
+    Mib,
+    /// Gibibytes, i.e. 1024³, e.g. "7.71 GiB".
+    Gib,
+    /// Megabytes, i.e. 1000², e.g. "8273 MB".
+    Mb,
+    /// Gigabytes, i.e. 1000³, e.g. "8.27 GB".
+    Gb,
+}
+
+impl SizeUnit {
+    /// Render `bytes` in this unit, with two decimals for the larger units.
+    pub fn format(self, bytes: u64) -> String {
+        let bytes = bytes as f64;
+        match self {
+            SizeUnit::Mib => format!("{:.0} MiB", bytes / 1024_f64.powi(2)),
+            SizeUnit::Gib => format!("{:.2} GiB", bytes / 1024_f64.powi(3)),
+            SizeUnit::Mb => format!("{:.0} MB", bytes / 1e6),
+            SizeUnit::Gb => format!("{:.2} GB", bytes / 1e9),
+        }
+    }
+}
+
+impl FromStr for SizeUnit {
+    type Err = String;
+
+    fn from_str(unit: &str) -> Result<Self, Self::Err> {
+        match unit.to_lowercase().as_str() {
+            "mib" => Ok(SizeUnit::Mib),
+            "gib" => Ok(SizeUnit::Gib),
+            "mb" => Ok(SizeUnit::Mb),
+            "gb" => Ok(SizeUnit::Gb),
+            _ => Err(format!("`{}` is not one of: mib, gib, mb, gb", unit)),
+        }
+    }
+}
+
+impl fmt::Display for MemoryInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display(SizeUnit::default(), false))
+    }
+}
+
+/// Read the memory usage of the system under the root from `opts`. In a
+/// container, whose cgroup limits the memory below the total memory of
+/// the host, the usage of the cgroup is read instead.
+pub fn read(opts: &Options) -> Result<MemoryInfo, FetchError> {
+    let contents = crate::read_traced(opts, "/proc/meminfo")?;
+    let meminfo = parse_meminfo(&contents);
+    let field = |key: &str| {
+        meminfo
+            .get(key)
+            .copied()
+            .ok_or_else(|| FetchError::ParseError(format!("/proc/meminfo has no {}", key)))
+    };
+
+    let total = field("MemTotal")?;
+    let used = match field("MemAvailable") {
+        Ok(available) => total.saturating_sub(available),
+        // Kernels older than 3.14 do not estimate the available memory,
+        // so count the free memory, buffers and caches ourselves.
+        Err(_) => {
+            let free = field("MemFree")?;
+            let buffers = field("Buffers").unwrap_or(0);
+            let cached = field("Cached").unwrap_or(0);
+            let reclaimable = field("SReclaimable").unwrap_or(0);
+            let shmem = field("Shmem").unwrap_or(0);
+            (total + shmem).saturating_sub(free + buffers + cached + reclaimable)
+        }
+    };
+    let host = MemoryInfo { used, total };
+
+    match opts.attempt("cgroup", || cgroup_memory(opts)) {
+        Ok(cgroup) if cgroup.total < host.total => Ok(cgroup),
+        _ => Ok(host),
+    }
+}
+
+/// Parse `/proc/meminfo` into the values of its fields, in bytes.
+fn parse_meminfo(meminfo: &str) -> HashMap<&str, u64> {
+    meminfo
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let kib: u64 = value.trim().trim_end_matches("kB").trim().parse().ok()?;
+            Some((key, kib * 1024))
+        })
+        .collect()
+}
+
+/// The memory usage, and limit of the cgroup of `fetchit`, with either
+/// cgroup v2, or the memory controller of cgroup v1.
+fn cgroup_memory(opts: &Options) -> Result<MemoryInfo, FetchError> {
+    let root = opts.root();
+    let cgroups = root.read_to_string("/proc/self/cgroup")?;
+
+    // Lines like "0::/user.slice" for v2, or "4:memory:/docker/ab12" for v1.
+    let (mount, path, files) = cgroups
+        .lines()
+        .find_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            let (_, controllers, path) = (fields.next()?, fields.next()?, fields.next()?);
+            if controllers.is_empty() {
+                Some((
+                    "/sys/fs/cgroup",
+                    path,
+                    ("memory.max", "memory.current", "inactive_file"),
+                ))
+            } else if controllers
+                .split(',')
+                .any(|controller| controller == "memory")
+            {
+                Some((
+                    "/sys/fs/cgroup/memory",
+                    path,
+                    (
+                        "memory.limit_in_bytes",
+                        "memory.usage_in_bytes",
+                        "total_inactive_file",
+                    ),
+                ))
+            } else {
+                None
+            }
+        })
+        .ok_or_else(|| FetchError::NotFound("no memory cgroup".to_string()))?;
+    let (limit_file, usage_file, inactive_key) = files;
+
+    // Without a cgroup namespace, e.g. Docker with cgroup v1, the path is
+    // the one on the host, like "/docker/ab12", while the container only
+    // sees its own cgroup, mounted at the root of the hierarchy.
+    let joined = format!("{}{}", mount, path.trim_end_matches('/'));
+    let dir = if root.exists(format!("{}/{}", joined, limit_file)) {
+        joined.as_str()
+    } else {
+        mount
+    };
+
+    let read_bytes = |file: &str| -> Result<u64, FetchError> {
+        let path = format!("{}/{}", dir, file);
+        let value = root.read_to_string(&path)?;
+        match value.trim() {
+            "max" => Err(FetchError::NotFound(format!("{} is unlimited", path))),
+            value => value
+                .parse()
+                .map_err(|_| FetchError::ParseError(format!("invalid value in {}", path))),
+        }
+    };
+
+    let total = read_bytes(limit_file)?;
+    // The page cache counts against the cgroup, but the kernel reclaims
+    // it, before running out of memory, just like `docker stats` shows.
+    let inactive = root
+        .read_to_string(format!("{}/memory.stat", dir))
+        .ok()
+        .and_then(|stat| {
+            stat.lines().find_map(|line| {
+                let (key, value) = line.split_once(' ')?;
+                if key == inactive_key {
+                    value.trim().parse().ok()
+                } else {
+                    None
+                }
+            })
+        })
+        .unwrap_or(0);
+    let used = read_bytes(usage_file)?.saturating_sub(inactive);
+    Ok(MemoryInfo { used, total })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    fn detect(fixture: &Fixture) -> MemoryInfo {
+        read(&Options::default().with_root(fixture.root())).unwrap()
+    }
+
+    #[test]
+    fn available_and_legacy() {
+        let modern = Fixture::new().file(
+            "/proc/meminfo",
+            "MemTotal:        8388608 kB\nMemFree:         1048576 kB\n\
+             MemAvailable:    6291456 kB\n",
+        );
+        let memory = detect(&modern);
+        assert_eq!(
+            memory,
+            MemoryInfo {
+                used: 2 * GIB,
+                total: 8 * GIB
+            }
+        );
+        assert_eq!(
+            memory.display(SizeUnit::Gib, true),
+            "2.00 GiB / 8.00 GiB (25%)"
+        );
+        assert_eq!(memory.display(SizeUnit::Mb, false), "2147 MB / 8590 MB");
+
+        let legacy = Fixture::new().file(
+            "/proc/meminfo",
+            "MemTotal: 8388608 kB\nMemFree: 2097152 kB\nBuffers: 1048576 kB\n\
+             Cached: 2097152 kB\nShmem: 1048576 kB\n",
+        );
+        assert_eq!(detect(&legacy).used, 4 * GIB);
+    }
+
+    #[test]
+    fn cgroup_limits() {
+        let meminfo = "MemTotal: 8388608 kB\nMemAvailable: 6291456 kB\n";
+        let v2 = Fixture::new()
+            .file("/proc/meminfo", meminfo)
+            .file("/proc/self/cgroup", "0::/\n")
+            .file("/sys/fs/cgroup/memory.max", &format!("{}\n", GIB))
+            .file(
+                "/sys/fs/cgroup/memory.current",
+                &format!("{}\n", GIB / 2 + 4096),
+            )
+            .file("/sys/fs/cgroup/memory.stat", "anon 1\ninactive_file 4096\n");
+        assert_eq!(
+            detect(&v2),
+            MemoryInfo {
+                used: GIB / 2,
+                total: GIB
+            }
+        );
+
+        // Docker with cgroup v1, and without a cgroup namespace, which
+        // mounts the cgroup of the container at the root of the hierarchy.
+        let v1 = |limit: u64| {
+            Fixture::new()
+                .file("/proc/meminfo", meminfo)
+                .file(
+                    "/proc/self/cgroup",
+                    "12:cpu:/docker/ab12\n4:memory:/docker/ab12\n",
+                )
+                .file(
+                    "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+                    &format!("{}\n", limit),
+                )
+                .file(
+                    "/sys/fs/cgroup/memory/memory.usage_in_bytes",
+                    &format!("{}\n", GIB / 4 + 8192),
+                )
+                .file(
+                    "/sys/fs/cgroup/memory/memory.stat",
+                    "cache 8192\ntotal_inactive_file 8192\n",
+                )
+        };
+        assert_eq!(
+            detect(&v1(2 * GIB)),
+            MemoryInfo {
+                used: GIB / 4,
+                total: 2 * GIB
+            }
+        );
+        assert_eq!(detect(&v1(9223372036854771712)).total, 8 * GIB);
+    }
+}
@@ -0,0 +1,575 @@
+// @Brief : This file contains the `InfoModule` trait, and the registry
+//          of modules, that decides which fields `fetchit` displays.
+
+use crate::cache::CachePolicy;
+use crate::{deadline, Attempt, FetchError, Options, Trace};
+use colored::Color;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A single line of system info, e.g. the OS name, or the uptime.
+///
+/// Implement this trait to add a custom field to the output, and
+/// push it into a [`Registry`].
+pub trait InfoModule: Send + Sync {
+    /// Identifier used to select the module, e.g. "os".
+    fn name(&self) -> &str;
+
+    /// Text displayed in front of the value, e.g. "OS".
+    fn label(&self) -> &str;
+
+    /// Nerd Font glyph displayed between the label and the value.
+    fn icon(&self) -> &str;
+
+    /// Color used for the label and the icon.
+    fn color(&self) -> Color;
+
+    /// Probe the system, and return the value to display.
+    fn collect(&self, opts: &Options) -> Result<String, FetchError>;
+
+    /// How long the value can be served from the cache, instead of being
+    /// collected again, or `None` if it must never be cached.
+    fn cache_policy(&self) -> Option<CachePolicy> {
+        None
+    }
+}
+
+/// A module built into `fetchit`, backed by one of the library probes.
+#[derive(Clone, Copy)]
+struct Builtin {
+    name: &'static str,
+    label: &'static str,
+    icon: &'static str,
+    color: Color,
+    probe: fn(&Options) -> Result<String, FetchError>,
+    cache: Option<BuiltinCache>,
+}
+
+/// The [`CachePolicy`] of a built-in module.
+#[derive(Clone, Copy)]
+struct BuiltinCache {
+    ttl: Duration,
+    watch: &'static [&'static str],
+}
+
+impl InfoModule for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn label(&self) -> &str {
+        self.label
+    }
+
+    fn icon(&self) -> &str {
+        self.icon
+    }
+
+    fn color(&self) -> Color {
+        self.color
+    }
+
+    fn collect(&self, opts: &Options) -> Result<String, FetchError> {
+        (self.probe)(opts)
+    }
+
+    fn cache_policy(&self) -> Option<CachePolicy> {
+        self.cache.map(|cache| CachePolicy {
+            ttl: cache.ttl,
+            watch: cache.watch.iter().map(PathBuf::from).collect(),
+        })
+    }
+}
+
+/// All the built-in modules, in the order they are displayed by default.
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "os",
+        label: "OS",
+        icon: "\u{f18e}",
+        color: Color::Red,
+        probe: crate::get_os_name,
+        cache: Some(BuiltinCache {
+            ttl: Duration::from_secs(24 * 60 * 60),
+            watch: &["/etc/os-release", "/usr/lib/os-release", "/etc/lsb-release"],
+        }),
+    },
+    Builtin {
+        name: "kernel",
+        label: "KERNEL",
+        icon: "\u{e712}",
+        color: Color::Magenta,
+        probe: |opts| crate::kernel::read(opts).map(|kernel| kernel.display(opts.kernel_format())),
+        cache: None,
+    },
+    Builtin {
+        name: "shell",
+        label: "SHELL",
+        icon: "\u{f155}",
+        color: Color::Yellow,
+        probe: crate::get_shell_name,
+        cache: None,
+    },
+    Builtin {
+        name: "session",
+        label: "SESSION",
+        icon: "\u{f821}",
+        color: Color::Blue,
+        probe: crate::get_session_name,
+        cache: None,
+    },
+    Builtin {
+        name: "uptime",
+        label: "UPTIME",
+        icon: "\u{fa1a}",
+        color: Color::Cyan,
+        probe: |opts| crate::get_sys_uptime(opts).map(crate::format_uptime),
+        cache: None,
+    },
+    Builtin {
+        name: "packages",
+        label: "PACKAGES",
+        icon: "\u{f8d6}",
+        color: Color::Green,
+        probe: |opts| crate::packages::get_num_packages(opts).map(|count| count.to_string()),
+        // The databases of the package managers, that change whenever
+        // a package is installed, or removed. rpm updates its database
+        // files in place, which leaves the mtime of their directory alone,
+        // and Gentoo only touches the category of a new package.
+        cache: Some(BuiltinCache {
+            ttl: Duration::from_secs(24 * 60 * 60),
+            watch: &[
+                "/var/lib/pacman/local",
+                "/var/lib/dpkg/status",
+                "/var/lib/rpm/rpmdb.sqlite",
+                "/var/lib/rpm/Packages",
+                "/usr/lib/sysimage/rpm/rpmdb.sqlite",
+                "/usr/lib/sysimage/rpm/Packages",
+                "/var/db/pkg/*",
+                "/var/lib/scratchpkg/db",
+                "/var/lib/eopkg/package",
+                "/var/db/xbps",
+                "/nix/var/nix/profiles",
+            ],
+        }),
+    },
+    Builtin {
+        name: "hostname",
+        label: "HOSTNAME",
+        icon: "\u{f277}",
+        color: Color::White,
+        probe: crate::get_hostname,
+        cache: None,
+    },
+    Builtin {
+        name: "init",
+        label: "INIT",
+        icon: "\u{f013}",
+        color: Color::BrightYellow,
+        probe: |opts| crate::init::read(opts).map(|init| init.to_string()),
+        cache: None,
+    },
+    Builtin {
+        name: "virt",
+        label: "VIRT",
+        icon: "\u{f1b2}",
+        color: Color::BrightCyan,
+        probe: |opts| crate::virt::read(opts).map(|virt| virt.to_string()),
+        cache: None,
+    },
+    Builtin {
+        name: "host",
+        label: "HOST",
+        icon: "\u{f109}",
+        color: Color::BrightBlue,
+        probe: |opts| crate::host::read(opts).map(|host| host.to_string()),
+        cache: None,
+    },
+    Builtin {
+        name: "cpu",
+        label: "CPU",
+        icon: "\u{f2db}",
+        color: Color::BrightMagenta,
+        probe: |opts| crate::cpu::read(opts).map(|cpu| cpu.to_string()),
+        cache: None,
+    },
+    Builtin {
+        name: "memory",
+        label: "MEMORY",
+        icon: "\u{f85a}",
+        color: Color::BrightGreen,
+        probe: |opts| {
+            crate::memory::read(opts)
+                .map(|memory| memory.display(opts.memory_unit(), opts.memory_percent()))
+        },
+        cache: None,
+    },
+    Builtin {
+        name: "swap",
+        label: "SWAP",
+        icon: "\u{f0ec}",
+        color: Color::BrightRed,
+        probe: |opts| {
+            crate::swap::read(opts)
+                .map(|swap| swap.display(opts.memory_unit(), opts.memory_percent()))
+        },
+        cache: None,
+    },
+    Builtin {
+        name: "gpu",
+        label: "GPU",
+        icon: "\u{f878}",
+        color: Color::BrightBlue,
+        probe: |opts| {
+            crate::gpu::read(opts).map(|gpus| {
+                let gpus: Vec<String> = gpus.iter().map(|gpu| gpu.to_string()).collect();
+                gpus.join(", ")
+            })
+        },
+        cache: None,
+    },
+    Builtin {
+        name: "disk",
+        label: "DISK",
+        icon: "\u{f0a0}",
+        color: Color::BrightYellow,
+        probe: |opts| {
+            crate::disk::read(opts).map(|disks| {
+                let disks: Vec<String> = disks.iter().map(|disk| disk.to_string()).collect();
+                disks.join(", ")
+            })
+        },
+        cache: None,
+    },
+    Builtin {
+        name: "battery",
+        label: "BATTERY",
+        icon: "\u{f240}",
+        color: Color::Green,
+        probe: |opts| crate::battery::read(opts).map(|battery| battery.to_string()),
+        cache: None,
+    },
+    Builtin {
+        name: "network",
+        label: "NETWORK",
+        icon: "\u{f6ff}",
+        color: Color::Cyan,
+        probe: |opts| {
+            crate::network::read(opts).map(|interfaces| {
+                let interfaces: Vec<String> =
+                    interfaces.iter().map(|iface| iface.to_string()).collect();
+                interfaces.join(", ")
+            })
+        },
+        cache: None,
+    },
+    Builtin {
+        name: "load",
+        label: "LOAD",
+        icon: "\u{f080}",
+        color: Color::Magenta,
+        probe: |opts| crate::load::read(opts).map(|load| load.display(opts.load_per_cpu())),
+        cache: None,
+    },
+];
+
+/// The built-in modules displayed, when none are selected, in order.
+const DEFAULT_MODULES: &[&str] = &[
+    "os", "kernel", "shell", "session", "uptime", "packages", "hostname",
+];
+
+/// An ordered list of modules, to be collected and displayed.
+pub struct Registry {
+    modules: Vec<Arc<dyn InfoModule>>,
+}
+
+impl Registry {
+    /// Create a registry without any modules.
+    pub fn new() -> Self {
+        Registry {
+            modules: Vec::new(),
+        }
+    }
+
+    /// Names of all the built-in modules, including the ones, that are
+    /// not displayed by default.
+    pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+        BUILTINS.iter().map(|module| module.name)
+    }
+
+    /// Create a registry with the given built-in modules, in the given order.
+    ///
+    /// Returns the offending name, if it is not a built-in module.
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Result<Self, String> {
+        let mut registry = Registry::new();
+        for name in names {
+            let name = name.as_ref();
+            let module = BUILTINS
+                .iter()
+                .find(|module| module.name == name)
+                .ok_or_else(|| name.to_string())?;
+            registry.push(Arc::new(*module));
+        }
+        Ok(registry)
+    }
+
+    /// Append a module at the end of the registry.
+    pub fn push(&mut self, module: Arc<dyn InfoModule>) {
+        self.modules.push(module);
+    }
+
+    /// Remove the module with the given name, if it is present.
+    pub fn remove(&mut self, name: &str) -> Option<Arc<dyn InfoModule>> {
+        let index = self
+            .modules
+            .iter()
+            .position(|module| module.name() == name)?;
+        Some(self.modules.remove(index))
+    }
+
+    /// Iterate over the modules, in display order.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn InfoModule> {
+        self.modules.iter().map(|module| module.as_ref())
+    }
+
+    /// Collect every module concurrently, each in its own thread, and
+    /// return their outcomes in display order.
+    ///
+    /// A module that does not finish within its timeout from `opts`, is
+    /// reported as [`FetchError::Timeout`], instead of being waited for.
+    /// It keeps running in the background though, and still caches its
+    /// value once it finishes, as long as the process is alive by then.
+    /// `fetchit` itself exits right after printing, so there, only the
+    /// values, that finish in time, are ever cached.
+    pub fn collect(&self, opts: &Options) -> Vec<Result<String, FetchError>> {
+        // Start all the modules first, and only then wait for them, so
+        // that they run alongside each other. Values still valid in the
+        // cache are served right away, without collecting them again.
+        let pending: Vec<_> = self
+            .modules
+            .iter()
+            .map(|module| {
+                let timeout = opts.timeout_for(module.name());
+                let (module_clone, opts_clone) = (Arc::clone(module), opts.clone());
+                deadline::spawn(module.name(), timeout, move || {
+                    collect_cached(module_clone.as_ref(), &opts_clone)
+                })
+            })
+            .collect();
+
+        pending.into_iter().map(deadline::Pending::wait).collect()
+    }
+
+    /// Collect every module like [`Registry::collect`], but also record
+    /// every source each of them tried, for the `--doctor` mode.
+    pub fn diagnose(&self, opts: &Options) -> Vec<Diagnosis> {
+        let pending: Vec<_> = self
+            .modules
+            .iter()
+            .map(|module| {
+                let trace = Trace::new();
+                let timeout = opts.timeout_for(module.name());
+                let (module_clone, opts_clone) =
+                    (Arc::clone(module), opts.clone().with_trace(trace.clone()));
+                let outcome = deadline::spawn(module.name(), timeout, move || {
+                    let start = Instant::now();
+                    let result = collect_cached(module_clone.as_ref(), &opts_clone);
+                    Ok((result, start.elapsed()))
+                });
+                (module, trace, timeout, outcome)
+            })
+            .collect();
+
+        pending
+            .into_iter()
+            .map(|(module, trace, timeout, outcome)| {
+                let (result, elapsed) = outcome.wait().unwrap_or_else(|err| (Err(err), timeout));
+                Diagnosis {
+                    name: module.name().to_string(),
+                    result,
+                    elapsed,
+                    // The attempts of a module, that timed out, are the
+                    // ones it made so far.
+                    attempts: trace.attempts(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+}
+
+/// The outcome of a single module, along with every source it tried.
+#[derive(Debug, Clone)]
+pub struct Diagnosis {
+    pub name: String,
+    pub result: Result<String, FetchError>,
+    /// How long the module took, or its timeout, if it did not finish.
+    pub elapsed: Duration,
+    pub attempts: Vec<Attempt>,
+}
+
+/// Collect `module`, going through the cache from `opts`, if it has one,
+/// and the module allows caching.
+fn collect_cached(module: &dyn InfoModule, opts: &Options) -> Result<String, FetchError> {
+    let (cache, policy) = match (opts.cache(), module.cache_policy()) {
+        (Some(cache), Some(policy)) => (cache, policy),
+        _ => return module.collect(opts),
+    };
+
+    let cached = opts.attempt("cache", || {
+        cache
+            .get(module.name(), &policy, opts.root())
+            .ok_or_else(|| FetchError::NotFound("no valid value cached".to_string()))
+    });
+    if let Ok(value) = cached {
+        return Ok(value);
+    }
+    let value = module.collect(opts)?;
+    // Failing to write the cache only makes the next run slower.
+    let _ = cache.put(module.name(), &policy, opts.root(), &value);
+    Ok(value)
+}
+
+impl Default for Registry {
+    /// The default built-in modules, in their default order.
+    fn default() -> Self {
+        Registry::from_names(DEFAULT_MODULES).expect("built-in module names are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use crate::testutil::Fixture;
+
+    #[test]
+    fn select_and_reorder() {
+        let registry = Registry::from_names(&["uptime", "os"]).unwrap();
+        let names: Vec<_> = registry.iter().map(|module| module.name()).collect();
+        assert_eq!(names, ["uptime", "os"]);
+
+        assert_eq!(
+            Registry::from_names(&["os", "bogus"]).err(),
+            Some("bogus".to_string())
+        );
+    }
+
+    #[test]
+    fn remove_module() {
+        let mut registry = Registry::default();
+        assert_eq!(registry.len(), DEFAULT_MODULES.len());
+        assert!(registry.remove("packages").is_some());
+        assert!(registry.remove("packages").is_none());
+        assert!(registry.iter().all(|module| module.name() != "packages"));
+    }
+
+    // A module, that takes `delay` to collect its value, and allows it to
+    // be cached, if `cached` is set.
+    struct Slow {
+        name: &'static str,
+        delay: Duration,
+        cached: bool,
+    }
+
+    impl InfoModule for Slow {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn label(&self) -> &str {
+            "SLOW"
+        }
+
+        fn icon(&self) -> &str {
+            ""
+        }
+
+        fn color(&self) -> Color {
+            Color::White
+        }
+
+        fn collect(&self, _opts: &Options) -> Result<String, FetchError> {
+            std::thread::sleep(self.delay);
+            Ok(self.name.to_string())
+        }
+
+        fn cache_policy(&self) -> Option<CachePolicy> {
+            self.cached.then(|| CachePolicy {
+                ttl: Duration::from_secs(60),
+                watch: Vec::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn collect_with_timeout() {
+        let mut registry = Registry::new();
+        registry.push(Arc::new(Slow {
+            name: "hung",
+            delay: Duration::from_secs(5),
+            cached: false,
+        }));
+        registry.push(Arc::new(Slow {
+            name: "quick",
+            delay: Duration::from_millis(1),
+            cached: false,
+        }));
+
+        let opts = Options::default().with_field_timeout("hung", Duration::from_millis(50));
+        let start = Instant::now();
+        let collected = registry.collect(&opts);
+        assert!(matches!(collected[0], Err(FetchError::Timeout(_))));
+        assert_eq!(collected[1], Ok("quick".to_string()));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn timed_out_value_is_cached() {
+        let fixture = Fixture::new();
+        let mut registry = Registry::new();
+        registry.push(Arc::new(Slow {
+            name: "packages",
+            delay: Duration::from_millis(200),
+            cached: true,
+        }));
+        let opts = Options::default()
+            .with_root(fixture.root())
+            .with_cache(Cache::new(fixture.root().path("/cache")))
+            .with_timeout(Duration::from_millis(20));
+
+        // The module keeps running after its deadline, and caches its
+        // value once it finishes, so the next run is served right away.
+        assert!(matches!(
+            registry.collect(&opts)[0],
+            Err(FetchError::Timeout(_))
+        ));
+        std::thread::sleep(Duration::from_millis(500));
+        assert_eq!(registry.collect(&opts), [Ok("packages".to_string())]);
+    }
+
+    #[test]
+    fn diagnose_records_sources() {
+        let fixture = Fixture::new().file("/etc/hostname", "box\n");
+        let opts = Options::default().with_root(fixture.root());
+        let registry = Registry::from_names(&["hostname"]).unwrap();
+
+        let diagnosis = registry.diagnose(&opts).remove(0);
+        assert_eq!(diagnosis.result, Ok("box".to_string()));
+        let sources: Vec<_> = diagnosis
+            .attempts
+            .iter()
+            .map(|attempt| attempt.source.as_str())
+            .collect();
+        assert_eq!(sources, ["/proc/sys/kernel/hostname", "/etc/hostname"]);
+        assert!(diagnosis.attempts[0].outcome.is_err());
+    }
+}
@@ -0,0 +1,287 @@
+// @Brief : This file contains the active network interfaces, with their
+//          addresses, their kind, and the one the default route goes through.
+
+use crate::ifaddrs::{self, IfAddr};
+use crate::{FetchError, Options};
+use std::fmt;
+use std::net::IpAddr;
+
+/// A network interface, that is up, and has an address, e.g. "wlan0".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkInterface {
+    /// Name of the interface, e.g. "enp3s0".
+    pub name: String,
+    pub kind: InterfaceKind,
+    /// The IPv4 addresses, followed by the IPv6 ones, leaving out the link
+    /// local IPv6 addresses, which every interface has.
+    pub addresses: Vec<InterfaceAddress>,
+    /// Whether the default route, of either IPv4, or IPv6, goes through
+    /// this interface.
+    pub default_route: bool,
+}
+
+/// An address of an interface, along with its network prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceAddress {
+    pub address: IpAddr,
+    /// Length of the network prefix, e.g. 24 for "192.168.1.23/24".
+    pub prefix_len: u8,
+}
+
+impl fmt::Display for InterfaceAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+/// What an interface is connected through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceKind {
+    Ethernet,
+    Wifi,
+    Bridge,
+    /// A tunnel, e.g. WireGuard, OpenVPN's tun and tap devices, or PPP.
+    Vpn,
+    Other,
+}
+
+impl fmt::Display for InterfaceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InterfaceKind::Ethernet => "ethernet",
+            InterfaceKind::Wifi => "wifi",
+            InterfaceKind::Bridge => "bridge",
+            InterfaceKind::Vpn => "vpn",
+            InterfaceKind::Other => "other",
+        })
+    }
+}
+
+impl fmt::Display for NetworkInterface {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.name, self.kind)?;
+        if self.default_route {
+            f.write_str(", default")?;
+        }
+        f.write_str(")")?;
+        for address in &self.addresses {
+            write!(f, " {}", address)?;
+        }
+        Ok(())
+    }
+}
+
+/// The hardware types from `/sys/class/net/*/type`, i.e. `ARPHRD_*`.
+const ARPHRD_ETHER: u32 = 1;
+const ARPHRD_PPP: u32 = 512;
+const ARPHRD_LOOPBACK: u32 = 772;
+const ARPHRD_NONE: u32 = 65534;
+
+/// List the active interfaces, i.e. the ones, that are up, and have an
+/// address, with the one of the default route first.
+pub fn read(opts: &Options) -> Result<Vec<NetworkInterface>, FetchError> {
+    let addrs = opts.attempt("getifaddrs", || {
+        if opts.root().is_host() {
+            ifaddrs::getifaddrs().map_err(|err| FetchError::from_io("getifaddrs", err))
+        } else {
+            Err(FetchError::NotFound(
+                "`getifaddrs` only describes the running system".to_string(),
+            ))
+        }
+    })?;
+    interfaces(opts, addrs)
+}
+
+/// Group `addrs` by their interface, and describe each of them from
+/// `/sys/class/net`, and the routing tables.
+fn interfaces(opts: &Options, addrs: Vec<IfAddr>) -> Result<Vec<NetworkInterface>, FetchError> {
+    let active = (libc::IFF_UP | libc::IFF_RUNNING) as u32;
+    let default_routes = default_routes(opts);
+
+    let mut interfaces: Vec<NetworkInterface> = Vec::new();
+    for addr in addrs {
+        if addr.flags & active != active || addr.flags & libc::IFF_LOOPBACK as u32 != 0 {
+            continue;
+        }
+        if let IpAddr::V6(v6) = addr.address {
+            // fe80::/10, which is only reachable on the link itself.
+            if v6.segments()[0] & 0xffc0 == 0xfe80 {
+                continue;
+            }
+        }
+
+        let address = InterfaceAddress {
+            address: addr.address,
+            prefix_len: addr.prefix_len,
+        };
+        match interfaces.iter_mut().find(|iface| iface.name == addr.name) {
+            Some(iface) => iface.addresses.push(address),
+            None => {
+                let kind = match interface_kind(opts, &addr.name) {
+                    Some(kind) => kind,
+                    // Loopback devices, other than `lo`, e.g. in a netns.
+                    None => continue,
+                };
+                interfaces.push(NetworkInterface {
+                    default_route: default_routes.contains(&addr.name),
+                    kind,
+                    addresses: vec![address],
+                    name: addr.name,
+                });
+            }
+        }
+    }
+    if interfaces.is_empty() {
+        return Err(FetchError::NotFound(
+            "no network interface is up".to_string(),
+        ));
+    }
+
+    for iface in &mut interfaces {
+        iface
+            .addresses
+            .sort_by_key(|address| address.address.is_ipv6());
+    }
+    // Sorting is stable, so the other ones stay in the order of the kernel.
+    interfaces.sort_by_key(|iface| !iface.default_route);
+    Ok(interfaces)
+}
+
+/// The kind of the interface `name`, or `None` for a loopback device.
+fn interface_kind(opts: &Options, name: &str) -> Option<InterfaceKind> {
+    let dir = format!("/sys/class/net/{}", name);
+    let arp_type = crate::read_traced(opts, &format!("{}/type", dir))
+        .ok()
+        .and_then(|arp_type| arp_type.trim().parse::<u32>().ok());
+    // e.g. "DEVTYPE=wlan", which plain ethernet devices do not set.
+    let uevent = crate::read_traced(opts, &format!("{}/uevent", dir)).unwrap_or_default();
+    let devtype = uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("DEVTYPE="))
+        .map(str::trim);
+
+    let kind = match (devtype, arp_type) {
+        (_, Some(ARPHRD_LOOPBACK)) => return None,
+        (Some("wlan"), _) => InterfaceKind::Wifi,
+        (Some("bridge"), _) => InterfaceKind::Bridge,
+        (Some("wireguard"), _) | (_, Some(ARPHRD_NONE)) | (_, Some(ARPHRD_PPP)) => {
+            InterfaceKind::Vpn
+        }
+        // Tap devices are ethernet devices, driven by a VPN in userspace.
+        (_, Some(ARPHRD_ETHER)) if opts.root().exists(format!("{}/tun_flags", dir)) => {
+            InterfaceKind::Vpn
+        }
+        (_, Some(ARPHRD_ETHER)) if opts.root().exists(format!("{}/wireless", dir)) => {
+            InterfaceKind::Wifi
+        }
+        (_, Some(ARPHRD_ETHER)) => InterfaceKind::Ethernet,
+        _ => InterfaceKind::Other,
+    };
+    Some(kind)
+}
+
+/// The interfaces, that the IPv4, or IPv6, default route goes through.
+fn default_routes(opts: &Options) -> Vec<String> {
+    let mut interfaces = Vec::new();
+
+    // "Iface Destination Gateway Flags RefCnt Use Metric Mask ...", with
+    // the addresses in hex.
+    if let Ok(routes) = crate::read_traced(opts, "/proc/net/route") {
+        interfaces.extend(routes.lines().skip(1).filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [iface, "00000000", _, _, _, _, _, "00000000", ..] => Some(iface.to_string()),
+                _ => None,
+            }
+        }));
+    }
+
+    // "dest dest_len src src_len next_hop metric refcnt use flags iface",
+    // without a header.
+    if let Ok(routes) = crate::read_traced(opts, "/proc/net/ipv6_route") {
+        const RTF_REJECT: u32 = 0x0200;
+        interfaces.extend(routes.lines().filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [dest, "00", _, _, _, _, _, _, flags, iface] = fields.as_slice() else {
+                return None;
+            };
+            let flags = u32::from_str_radix(flags, 16).ok()?;
+            // The kernel adds an unreachable default route through `lo`.
+            let default = dest.bytes().all(|digit| digit == b'0') && flags & RTF_REJECT == 0;
+            (default && *iface != "lo").then(|| iface.to_string())
+        }));
+    }
+    interfaces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    fn addr(name: &str, address: &str, prefix_len: u8) -> IfAddr {
+        IfAddr {
+            name: name.to_string(),
+            flags: (libc::IFF_UP | libc::IFF_RUNNING) as u32,
+            address: address.parse().unwrap(),
+            prefix_len,
+        }
+    }
+
+    #[test]
+    fn kinds_and_default_route() {
+        let fixture = Fixture::new()
+            .file("/sys/class/net/lo/type", "772\n")
+            .file("/sys/class/net/enp3s0/type", "1\n")
+            .file("/sys/class/net/enp3s0/uevent", "INTERFACE=enp3s0\nIFINDEX=2\n")
+            .file("/sys/class/net/wlan0/type", "1\n")
+            .file("/sys/class/net/wlan0/uevent", "DEVTYPE=wlan\nINTERFACE=wlan0\n")
+            .file("/sys/class/net/docker0/type", "1\n")
+            .file("/sys/class/net/docker0/uevent", "DEVTYPE=bridge\n")
+            .file("/sys/class/net/wg0/type", "65534\n")
+            .file("/sys/class/net/wg0/uevent", "DEVTYPE=wireguard\n")
+            .file(
+                "/proc/net/route",
+                "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                 wlan0\t0001A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\t0\t0\t0\n\
+                 wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n",
+            )
+            .file(
+                "/proc/net/ipv6_route",
+                "00000000000000000000000000000000 00 00000000000000000000000000000000 00 \
+                 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200       lo\n",
+            );
+        let opts = Options::default().with_root(fixture.root());
+
+        let mut down = addr("enp3s0", "10.0.0.2", 8);
+        down.flags = libc::IFF_UP as u32;
+        let mut loopback = addr("lo", "127.0.0.1", 8);
+        loopback.flags |= libc::IFF_LOOPBACK as u32;
+        let addrs = vec![
+            loopback,
+            down,
+            addr("docker0", "172.17.0.1", 16),
+            addr("wlan0", "fe80::1c2d:3e4f:5a6b:7c8d", 64),
+            addr("wlan0", "2a01:db8::23", 64),
+            addr("wlan0", "192.168.1.23", 24),
+            addr("wg0", "10.8.0.2", 24),
+        ];
+
+        let found: Vec<String> = interfaces(&opts, addrs)
+            .unwrap()
+            .iter()
+            .map(NetworkInterface::to_string)
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "wlan0 (wifi, default) 192.168.1.23/24 2a01:db8::23/64",
+                "docker0 (bridge) 172.17.0.1/16",
+                "wg0 (vpn) 10.8.0.2/24",
+            ]
+        );
+
+        assert!(interfaces(&opts, Vec::new()).unwrap_err().is_not_found());
+        assert!(read(&opts).unwrap_err().is_not_found());
+    }
+}
@@ -0,0 +1,307 @@
+// @Brief : This file contains the parser for `os-release(5)`, which
+//          identifies the distribution, e.g. its name, ID and version.
+
+use crate::{FetchError, Options};
+use colored::Color;
+use std::collections::HashMap;
+
+/// The locations of the os-release file, in the order they are tried.
+/// The second one is only read, if the first one does not exist.
+pub const PATHS: [&str; 2] = ["/etc/os-release", "/usr/lib/os-release"];
+
+/// The identity of the distribution, from its os-release file.
+///
+/// The fields, that are not set in the file, are `None`, and the
+/// remaining ones are available through [`OsRelease::get`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OsRelease {
+    /// `NAME`, e.g. "Fedora Linux".
+    pub name: Option<String>,
+    /// `PRETTY_NAME`, e.g. "Fedora Linux 36 (Workstation Edition)".
+    pub pretty_name: Option<String>,
+    /// `ID`, e.g. "fedora".
+    pub id: Option<String>,
+    /// `ID_LIKE`, the IDs of the distributions, this one is derived
+    /// from, closest first, e.g. ["ubuntu", "debian"] for Linux Mint.
+    pub id_like: Vec<String>,
+    /// `VERSION_ID`, e.g. "36".
+    pub version_id: Option<String>,
+    /// `VERSION_CODENAME`, e.g. "bookworm".
+    pub version_codename: Option<String>,
+    /// `BUILD_ID`, mostly set by rolling releases, e.g. "rolling".
+    pub build_id: Option<String>,
+    /// `VARIANT`, e.g. "Workstation Edition".
+    pub variant: Option<String>,
+    /// `ANSI_COLOR`, an SGR sequence for the color of the distribution,
+    /// e.g. "0;38;2;60;110;180".
+    pub ansi_color: Option<String>,
+    /// `LOGO`, the name of the icon of the distribution, e.g.
+    /// "fedora-logo-icon".
+    pub logo: Option<String>,
+    fields: HashMap<String, String>,
+}
+
+impl OsRelease {
+    /// Parse the contents of an os-release file.
+    ///
+    /// Lines, that are not an assignment of a valid variable name, are
+    /// ignored, just like comments and blank lines.
+    pub fn parse(contents: &str) -> Self {
+        let fields: HashMap<String, String> = contents
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (key, value) = line.split_once('=')?;
+                let valid_key =
+                    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid_key {
+                    return None;
+                }
+                Some((key.to_string(), unquote(value)?))
+            })
+            .collect();
+
+        let get = |key: &str| fields.get(key).filter(|value| !value.is_empty()).cloned();
+        OsRelease {
+            name: get("NAME"),
+            pretty_name: get("PRETTY_NAME"),
+            id: get("ID"),
+            id_like: get("ID_LIKE")
+                .map(|ids| ids.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+            version_id: get("VERSION_ID"),
+            version_codename: get("VERSION_CODENAME"),
+            build_id: get("BUILD_ID"),
+            variant: get("VARIANT"),
+            ansi_color: get("ANSI_COLOR"),
+            logo: get("LOGO"),
+            fields,
+        }
+    }
+
+    /// The color of the distribution, from `ANSI_COLOR`, if it is set, and
+    /// holds a foreground color.
+    pub fn color(&self) -> Option<Color> {
+        parse_sgr_color(self.ansi_color.as_deref()?)
+    }
+
+    /// The value of any variable in the file, e.g. "HOME_URL".
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+}
+
+/// Read the os-release file of the system under the root from `opts`,
+/// from `/etc/os-release`, or from `/usr/lib/os-release` if the former
+/// does not exist.
+pub fn read(opts: &Options) -> Result<OsRelease, FetchError> {
+    let mut result = Err(FetchError::NotFound("no os-release file".to_string()));
+    for path in PATHS {
+        result = opts.attempt(path, || {
+            let contents = opts.root().read_to_string(path)?;
+            Ok(OsRelease::parse(&contents))
+        });
+        // Any other error means, that the file exists, but is broken, in
+        // which case the other one must not be used instead.
+        if !matches!(&result, Err(err) if err.is_not_found()) {
+            break;
+        }
+    }
+    result
+}
+
+/// The foreground color, set by an SGR sequence, e.g. "1;31" or
+/// "0;38;2;23;147;209". Bold basic colors are taken as their bright
+/// variant, like most terminals display them.
+fn parse_sgr_color(sgr: &str) -> Option<Color> {
+    let codes: Vec<u8> = sgr
+        .split(';')
+        .map(|code| code.trim().parse().unwrap_or(0))
+        .collect();
+    let basic = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::White,
+    ];
+    let bright = [
+        Color::BrightBlack,
+        Color::BrightRed,
+        Color::BrightGreen,
+        Color::BrightYellow,
+        Color::BrightBlue,
+        Color::BrightMagenta,
+        Color::BrightCyan,
+        Color::BrightWhite,
+    ];
+
+    let mut bold = false;
+    let mut color = None;
+    let mut codes = codes.into_iter();
+    while let Some(code) = codes.next() {
+        match code {
+            0 => bold = false,
+            1 => bold = true,
+            30..=37 => {
+                let i = usize::from(code - 30);
+                color = Some((basic[i], bright[i]));
+            }
+            90..=97 => {
+                let i = usize::from(code - 90);
+                color = Some((bright[i], bright[i]));
+            }
+            38 => match codes.next() {
+                Some(2) => {
+                    let (r, g, b) = (codes.next()?, codes.next()?, codes.next()?);
+                    let true_color = Color::TrueColor { r, g, b };
+                    color = Some((true_color, true_color));
+                }
+                Some(5) => {
+                    let indexed = indexed_color(codes.next()?, &basic, &bright);
+                    color = Some((indexed, indexed));
+                }
+                _ => return None,
+            },
+            _ => {}
+        }
+    }
+    color.map(|(normal, bold_color)| if bold { bold_color } else { normal })
+}
+
+/// One of the 256 colors of `38;5;n`, i.e. the 16 basic colors, a
+/// 6x6x6 color cube, and 24 shades of grey.
+fn indexed_color(index: u8, basic: &[Color; 8], bright: &[Color; 8]) -> Color {
+    let cube = |level: u8| if level == 0 { 0 } else { 55 + level * 40 };
+    match index {
+        0..=7 => basic[usize::from(index)],
+        8..=15 => bright[usize::from(index - 8)],
+        16..=231 => {
+            let index = index - 16;
+            Color::TrueColor {
+                r: cube(index / 36),
+                g: cube(index / 6 % 6),
+                b: cube(index % 6),
+            }
+        }
+        _ => {
+            let grey = 8 + (index - 232) * 10;
+            Color::TrueColor {
+                r: grey,
+                g: grey,
+                b: grey,
+            }
+        }
+    }
+}
+
+/// Unquote a value, following the shell like rules of os-release, i.e.
+/// single quotes keep everything, double quotes allow escaping `"`, `\`,
+/// `$` and `` ` `` with a backslash, and outside of quotes, a backslash
+/// escapes any character. Returns `None` for an unterminated quote.
+fn unquote(value: &str) -> Option<String> {
+    let mut unquoted = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => loop {
+                match chars.next()? {
+                    '\'' => break,
+                    c => unquoted.push(c),
+                }
+            },
+            '"' => loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        c @ ('"' | '\\' | '$' | '`') => unquoted.push(c),
+                        c => {
+                            unquoted.push('\\');
+                            unquoted.push(c);
+                        }
+                    },
+                    c => unquoted.push(c),
+                }
+            },
+            '\\' => unquoted.push(chars.next()?),
+            c => unquoted.push(c),
+        }
+    }
+    Some(unquoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    #[test]
+    fn quoting_and_escapes() {
+        let os_release = OsRelease::parse(
+            "# A comment\n\
+             NAME=\"Linux Mint\"\n\
+             PRETTY_NAME='Mint = \"fresh\"'\n\
+             ID=linuxmint\n\
+             ID_LIKE=\"ubuntu debian\"\n\
+             VERSION_ID=\"21\"\n\
+             VARIANT=\"say \\\"hi\\\" \\\\ \\$HOME\"\n\
+             BUILD_ID=a\\ b\n\
+             ANSI_COLOR=\"0;32\"\n\
+             not a variable\n\
+             LOGO=\"unterminated\n",
+        );
+
+        assert_eq!(os_release.name.as_deref(), Some("Linux Mint"));
+        assert_eq!(os_release.pretty_name.as_deref(), Some("Mint = \"fresh\""));
+        assert_eq!(os_release.id.as_deref(), Some("linuxmint"));
+        assert_eq!(os_release.id_like, ["ubuntu", "debian"]);
+        assert_eq!(os_release.version_id.as_deref(), Some("21"));
+        assert_eq!(os_release.variant.as_deref(), Some("say \"hi\" \\ $HOME"));
+        assert_eq!(os_release.build_id.as_deref(), Some("a b"));
+        assert_eq!(os_release.ansi_color.as_deref(), Some("0;32"));
+        assert_eq!(os_release.logo, None);
+        assert_eq!(os_release.get("VERSION_CODENAME"), None);
+    }
+
+    #[test]
+    fn ansi_color() {
+        let color = |sgr: &str| OsRelease::parse(&format!("ANSI_COLOR=\"{}\"", sgr)).color();
+
+        assert_eq!(
+            color("0;38;2;23;147;209"),
+            Some(Color::TrueColor {
+                r: 23,
+                g: 147,
+                b: 209
+            })
+        );
+        assert_eq!(color("0;31"), Some(Color::Red));
+        assert_eq!(color("1;31"), Some(Color::BrightRed));
+        assert_eq!(color("38;5;12"), Some(Color::BrightBlue));
+        assert_eq!(
+            color("38;5;196"),
+            Some(Color::TrueColor { r: 255, g: 0, b: 0 })
+        );
+        assert_eq!(color("1"), None);
+        assert_eq!(OsRelease::default().color(), None);
+    }
+
+    #[test]
+    fn usr_lib_fallback() {
+        let fixture = Fixture::new().file("/usr/lib/os-release", "ID=arch\n");
+        let opts = Options::default().with_root(fixture.root());
+        assert_eq!(read(&opts).unwrap().id.as_deref(), Some("arch"));
+
+        // `/etc/os-release` takes precedence, when it exists.
+        let fixture = fixture.file("/etc/os-release", "ID=gentoo\n");
+        let opts = Options::default().with_root(fixture.root());
+        assert_eq!(read(&opts).unwrap().id.as_deref(), Some("gentoo"));
+    }
+}
@@ -3,133 +3,376 @@
 // @Brief : This file, contains the logic to get the
 //          number of packages installed on the system.
 
-use std::process::{Command, Stdio}; // For executing shell commands.
-
-pub fn get_num_packages() -> u32 {
-    let num_packages = packages_generic("pacman", &["-Q"])
-        .or_else(|_| packages_debian_based())
-        .or_else(|_| packges_fedora_based())
-        .or_else(|_| packages_generic("pkg", &["info"]))
-        .or_else(|_| packages_generic("ls", &["-d", "var/db/pkg/*/*"]))
-        .or_else(|_| packages_generic("ls", &["-d", "/var/lib/scratchpkg/db/*"]))
-        .or_else(|_| packages_generic("ls", &["/var/lib/eopkg/package/"]))
-        .or_else(|_| packages_generic("xbps-query", &["-l"]))
-        .or_else(|_| packages_generic("rpm", &["-qa"]))
-        .or_else(|_| packages_nixos_based())
-        .unwrap_or_else(|_| "Unknown".to_string());
-
-    // Count the total number of packages
-    let mut total_count: u32 = 0;
-    for _ in num_packages.lines() {
-        total_count += 1;
+use crate::error::ResultExt;
+use crate::{host_command, FetchError, Options};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+pub fn get_num_packages(opts: &Options) -> Result<u32, FetchError> {
+    // The package managers only describe the running system, so for any
+    // other root, count the entries in their databases instead.
+    if !opts.root().is_host() {
+        return packages_from_databases(opts);
     }
 
-    total_count
+    // The package managers list one package per line.
+    let generic = |cmd: &str, options: &[&str]| {
+        packages_generic(opts, cmd, options).map(|packages| packages.lines().count() as u32)
+    };
+    generic("pacman", &["-Q"])
+        .or_try(|| packages_debian_based(opts).map(|packages| packages.lines().count() as u32))
+        .or_try(|| packges_fedora_based(opts).map(|packages| packages.lines().count() as u32))
+        .or_try(|| generic("pkg", &["info"]))
+        // There is no command, that lists the packages of portage, scratchpkg
+        // or eopkg, so count the entries in their databases instead.
+        .or_try(|| portage_database(opts))
+        .or_try(|| directory_entries(opts, "/var/lib/scratchpkg/db"))
+        .or_try(|| directory_entries(opts, "/var/lib/eopkg/package"))
+        .or_try(|| generic("xbps-query", &["-l"]))
+        .or_try(|| packages_nixos_based(opts).map(|packages| packages.lines().count() as u32))
 }
 
-pub fn packages_generic(cmd: &str, options: &[&str]) -> Result<String, String> {
-    // Use `pkg info` to list the installed packages.
-    let packages = Command::new(cmd).args(options).output();
-
-    // Check if the above command executed, successfully,
-    // if so, unwrap the output from stdout, and return it.
-    match packages {
-        Ok(x) => Ok(String::from_utf8(x.stdout).unwrap()),
-        Err(e) => Err(e.to_string()),
-    }
+pub fn packages_generic(opts: &Options, cmd: &str, options: &[&str]) -> Result<String, FetchError> {
+    // Run the given command, e.g. `pkg info` to list the installed packages.
+    // If it executed successfully, return its output from stdout.
+    host_command(opts, cmd, options)
 }
 
-pub fn packages_debian_based() -> Result<String, String> {
-    // Getting the list of packages on a debian based system,
-    // requires the use of pipes, i.e. we will need to pipe the
-    // output from `dpkg-query -l` into `grep`, which turns out
-    // to be `dpkg-query -l | grep "^ii"`. So, we first use `Stdio::piped()`
-    // to write the output from `dpkg-query -l`.
-    let packages = Command::new("dpkg-query")
-        .arg("-l")
-        .stdout(Stdio::piped())
-        .spawn();
-
-    // Then, we check whether the command above ran successfully.
-    let packages_output = match packages {
-        Ok(mut x) => {
-            // If it did, then, take the output from `Stdio::piped()`.
-            if let Some(out) = x.stdout.take() {
-                // If the output was successfully taken from `Stdio::piped()`,
-                // then we pipe it into `grep`.
-                let grep_cmd = match Command::new("grep")
-                    .arg("^ii")
-                    .stdin(out)
-                    .stdout(Stdio::piped())
-                    .spawn()
-                {
-                    // Once the piping is successfully done, we use `wait_with_output`, to collect the
-                    // output and kill the process.
-                    Ok(y) => match y.wait_with_output() {
-                        Ok(z) => Ok(String::from_utf8(z.stdout).unwrap()),
-                        Err(e) => return Err(e.to_string()),
-                    },
-                    Err(e) => return Err(e.to_string()),
-                };
-                // If there are no errors, during this piping process,
-                // we return the output from `grep`, which will then
-                // be assigned to `packages_output`.
-                grep_cmd
-            } else {
-                return Err("Unknown".to_string());
-            }
-        }
-        Err(e) => return Err(e.to_string()),
-    };
+pub fn packages_debian_based(opts: &Options) -> Result<String, FetchError> {
+    // Getting the list of packages on a debian based system, is
+    // the equivalent of `dpkg-query -l | grep "^ii"`, i.e. we keep
+    // only the lines for packages that are actually installed.
+    let packages = host_command(opts, "dpkg-query", &["-l"])?;
 
-    // We return `packages_output`, i.e. list of packages installed.
-    packages_output
+    Ok(packages
+        .lines()
+        .filter(|line| line.starts_with("ii"))
+        .map(|line| format!("{}\n", line))
+        .collect())
 }
 
-pub fn packges_fedora_based() -> Result<String, String> {
-    // Use `yum list installed` to get the list of packages installed.
-    let packages = Command::new("yum").args(["list", "installed"]).output();
-
-    // Check if the above command executed, successfully,
-    // if so, unwrap the output from stdout, and return it.
-    match packages {
-        Ok(x) => Ok(String::from_utf8(x.stdout).unwrap()),
-        Err(_) => {
-            // If `yum list installed` fails, try running
-            // `rpm -qa` to get the list of installed packages.
-            let packages = Command::new("dnf").args(["list", "installed"]).output();
-            match packages {
-                Ok(x) => Ok(String::from_utf8(x.stdout).unwrap()),
-                Err(e) => Err(e.to_string()),
-            }
-        }
-    }
+pub fn packges_fedora_based(opts: &Options) -> Result<String, FetchError> {
+    // Use `rpm -qa` to get the list of packages installed, which only reads
+    // the local database, and if that fails, try `yum list installed`, and
+    // `dnf list installed`. Those load the metadata of the repositories
+    // first, which takes several seconds, i.e. longer than the timeout.
+    host_command(opts, "rpm", &["-qa"])
+        .or_try(|| host_command(opts, "yum", &["list", "installed"]))
+        .or_try(|| host_command(opts, "dnf", &["list", "installed"]))
 }
 
-pub fn packages_nixos_based() -> Result<String, String> {
+pub fn packages_nixos_based(opts: &Options) -> Result<String, FetchError> {
     // Use `nix-store -qR /run/current-system/sw/ 2>/dev/null && nix-store -qR ~/.nix-profile/`
     // to get the list of installed packages. So, we will first get the output for the
     // first command in the shell chain.
-    let packages = Command::new("nix-store")
-        .args(["-qR", "/run/current-system/sw/"])
-        .output();
-
-    match packages {
-        Ok(x) => {
-            // Once the first chain command succeeds, we will add it's output,
-            // to the second chain command.
-            let packages_output = String::from_utf8(x.stdout).unwrap();
-            match Command::new("nix-store")
-                .args(["-qR", "~/.nix-profile/"])
-                .output()
-            {
-                Ok(y) => {
-                    let prev_output = String::from_utf8(y.stdout).unwrap();
-                    Ok(format!("{}{}", packages_output, prev_output))
-                }
-                Err(e) => Err(e.to_string()),
+    let packages_output = host_command(opts, "nix-store", &["-qR", "/run/current-system/sw/"])?;
+
+    // Once the first chain command succeeds, we will add it's output,
+    // to the second chain command. There is no shell to expand `~`, so
+    // the profile is found from `$HOME` instead.
+    let home = crate::env_traced(opts, "HOME")?;
+    let profile = format!("{}/.nix-profile/", home.trim_end_matches('/'));
+    let prev_output = host_command(opts, "nix-store", &["-qR", &profile])?;
+
+    Ok(format!("{}{}", packages_output, prev_output))
+}
+
+/// Count the installed packages from the databases of the package
+/// managers under the root, e.g. of a mounted disk image.
+pub fn packages_from_databases(opts: &Options) -> Result<u32, FetchError> {
+    pacman_database(opts)
+        .or_try(|| dpkg_database(opts))
+        .or_try(|| portage_database(opts))
+        .or_try(|| directory_entries(opts, "/var/lib/scratchpkg/db"))
+        .or_try(|| directory_entries(opts, "/var/lib/eopkg/package"))
+        .or_try(|| xbps_database(opts))
+        .or_try(|| nix_profiles(opts))
+        .or_try(|| rpm_database(opts))
+}
+
+/// A directory for each package, next to the version of the database.
+fn pacman_database(opts: &Options) -> Result<u32, FetchError> {
+    let packages = directory_names(opts, "/var/lib/pacman/local")?;
+    Ok(packages
+        .iter()
+        .filter(|name| *name != "ALPM_DB_VERSION")
+        .count() as u32)
+}
+
+/// A paragraph for each package, including the removed ones, whose
+/// configuration files are still around, so only count installed ones.
+fn dpkg_database(opts: &Options) -> Result<u32, FetchError> {
+    let status = crate::read_traced(opts, "/var/lib/dpkg/status")?;
+    Ok(status
+        .lines()
+        .filter(|line| line.trim_end() == "Status: install ok installed")
+        .count() as u32)
+}
+
+/// A directory for each category, e.g. "app-shells", holding one for
+/// each of its packages.
+fn portage_database(opts: &Options) -> Result<u32, FetchError> {
+    let mut count = 0;
+    for category in directory_names(opts, "/var/db/pkg")? {
+        count += directory_entries(opts, &format!("/var/db/pkg/{}", category)).unwrap_or(0);
+    }
+    Ok(count)
+}
+
+/// A dictionary in `pkgdb-<version>.plist`, with the `state` of each
+/// package, i.e. "installed", or "unpacked" for a half installed one.
+fn xbps_database(opts: &Options) -> Result<u32, FetchError> {
+    // xbps keeps its database in `/var/db/xbps`, unless it is configured
+    // to use `/var/lib/xbps`.
+    let (dir, names) = directory_names(opts, "/var/db/xbps")
+        .map(|names| ("/var/db/xbps", names))
+        .or_try(|| Ok(("/var/lib/xbps", directory_names(opts, "/var/lib/xbps")?)))?;
+    let pkgdb = names
+        .iter()
+        .find(|name| name.starts_with("pkgdb-") && name.ends_with(".plist"))
+        .ok_or_else(|| FetchError::NotFound(format!("no pkgdb-*.plist in {}", dir)))?;
+    let plist = crate::read_traced(opts, &format!("{}/{}", dir, pkgdb))?;
+
+    // The plist is indented, and has one element on each line, but it
+    // does not have to, so the whitespace between the elements is dropped.
+    let plist: String = plist.split_whitespace().collect();
+    Ok(plist
+        .matches("<key>state</key><string>installed</string>")
+        .count() as u32)
+}
+
+/// The distinct store paths, that the programs of the NixOS system, and
+/// of the default profile, link to, e.g. "/nix/store/<hash>-bash-5.2".
+/// The dependencies of those, which `nix-store -qR` lists as well, are
+/// only recorded in the SQLite database of Nix, so they are left out.
+fn nix_profiles(opts: &Options) -> Result<u32, FetchError> {
+    let root = opts.root();
+    let mut store_paths = BTreeSet::new();
+    let mut profiles = 0;
+    for bin in [
+        "/nix/var/nix/profiles/system/sw/bin",
+        "/nix/var/nix/profiles/default/bin",
+    ] {
+        let Ok(bin) = opts.attempt(bin, || root.resolve(bin)) else {
+            continue;
+        };
+        let Ok(programs) = root.read_dir(&bin) else {
+            continue;
+        };
+        profiles += 1;
+        for program in programs {
+            let Ok(target) = root.read_link(bin.join(program)) else {
+                continue;
+            };
+            // "/nix/store/<hash>-<name>/bin/<program>".
+            let store_path: PathBuf = target.components().take(4).collect();
+            if store_path.starts_with("/nix/store") && store_path != Path::new("/nix/store") {
+                store_paths.insert(store_path);
             }
         }
-        Err(e) => Err(e.to_string()),
+    }
+    if profiles == 0 {
+        return Err(FetchError::NotFound(
+            "no profile in /nix/var/nix/profiles".to_string(),
+        ));
+    }
+    Ok(store_paths.len() as u32)
+}
+
+/// rpm keeps its packages in a Berkeley DB, or SQLite database, that only
+/// rpm itself can read, so the packages are not counted.
+fn rpm_database(opts: &Options) -> Result<u32, FetchError> {
+    let path = ["/usr/lib/sysimage/rpm", "/var/lib/rpm"]
+        .into_iter()
+        .find(|path| opts.root().exists(path))
+        .ok_or_else(|| FetchError::NotFound("no package database".to_string()))?;
+    opts.attempt(path, || {
+        Err(FetchError::NotFound(format!(
+            "the rpm database in {} can only be read by rpm",
+            path
+        )))
+    })
+}
+
+/// The number of entries in the directory at `path`, i.e. of packages
+/// for the managers, that keep a directory for each of them.
+fn directory_entries(opts: &Options, path: &str) -> Result<u32, FetchError> {
+    directory_names(opts, path).map(|packages| packages.len() as u32)
+}
+
+fn directory_names(opts: &Options, path: &str) -> Result<Vec<String>, FetchError> {
+    opts.attempt(path, || opts.root().read_dir(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::CannedRunner;
+    use crate::testutil::Fixture;
+    use std::sync::Arc;
+
+    // Options, that answer every command from `runner`.
+    fn canned(runner: CannedRunner) -> Options {
+        Options::default().with_runner(Arc::new(runner))
+    }
+
+    #[test]
+    fn debian_installed_only() {
+        let runner = CannedRunner::new().with(
+            "dpkg-query -l",
+            0,
+            "Desired=Unknown/Install/Remove/Purge/Hold\n\
+             ii  bash  5.2.15-2+b2  amd64  GNU Bourne Again SHell\n\
+             rc  foo   1.0-1        amd64  removed package\n\
+             ii  zsh   5.9-4+b2     amd64  shell with lots of features\n",
+        );
+        assert_eq!(get_num_packages(&canned(runner)), Ok(2));
+    }
+
+    #[test]
+    fn fedora_prefers_rpm() {
+        let runner = CannedRunner::new()
+            .with(
+                "rpm -qa",
+                0,
+                "bash-5.2.26-3.fc40.x86_64\nzsh-5.9-15.fc40.x86_64\n",
+            )
+            .with(
+                "yum list installed",
+                0,
+                "Installed Packages\nbash.x86_64  5.2.26-3.fc40  @anaconda\n",
+            );
+        assert_eq!(get_num_packages(&canned(runner)), Ok(2));
+    }
+
+    #[test]
+    fn other_root_counts_databases() {
+        let fixture = Fixture::new()
+            .file("/var/lib/pacman/local/ALPM_DB_VERSION", "9\n")
+            .dir("/var/lib/pacman/local/bash-5.2.026-2")
+            .dir("/var/lib/pacman/local/zsh-5.9-5");
+        // The package managers of the host are never asked.
+        let runner = CannedRunner::new().with("pacman -Q", 0, "bash 5.2\nzsh 5.9\nvim 9.1\n");
+        let opts = canned(runner).with_root(fixture.root());
+        assert_eq!(get_num_packages(&opts), Ok(2));
+
+        let debian = Fixture::new().file(
+            "/var/lib/dpkg/status",
+            "Package: bash\nStatus: install ok installed\n\n\
+             Package: foo\nStatus: deinstall ok config-files\n\n\
+             Package: zsh\nStatus: install ok installed\n",
+        );
+        let opts = Options::default().with_root(debian.root());
+        assert_eq!(get_num_packages(&opts), Ok(2));
+
+        let gentoo = Fixture::new()
+            .dir("/var/db/pkg/app-shells/bash-5.2_p26")
+            .dir("/var/db/pkg/app-shells/zsh-5.9-r3")
+            .dir("/var/db/pkg/sys-apps/portage-3.0.63");
+        let opts = Options::default().with_root(gentoo.root());
+        assert_eq!(get_num_packages(&opts), Ok(3));
+
+        let empty = Options::default().with_root(Fixture::new().root());
+        assert!(get_num_packages(&empty).unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn other_root_counts_xbps_and_nix() {
+        let void = Fixture::new().file(
+            "/var/db/xbps/pkgdb-0.38.plist",
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n<dict>\n\
+             \t<key>bash</key>\n\t<dict>\n\t\t<key>pkgver</key>\n\t\t<string>bash-5.2.21_1</string>\n\
+             \t\t<key>state</key>\n\t\t<string>installed</string>\n\t</dict>\n\
+             \t<key>vim</key>\n\t<dict>\n\t\t<key>state</key>\n\t\t<string>unpacked</string>\n\t</dict>\n\
+             \t<key>zsh</key>\n\t<dict>\n\t\t<key>state</key>\n\t\t<string>installed</string>\n\t</dict>\n\
+             </dict>\n</plist>\n",
+        );
+        let opts = Options::default().with_root(void.root());
+        assert_eq!(get_num_packages(&opts), Ok(2));
+
+        // The links of the profiles are absolute, so they have to resolve
+        // under the root, not on the host.
+        let nixos = Fixture::new()
+            .dir("/nix/var/nix/profiles")
+            .dir("/nix/store/0a1b-nixos-system")
+            .dir("/nix/store/2c3d-system-path/bin");
+        let root = nixos.root();
+        let link = |target: &str, path: &str| {
+            std::os::unix::fs::symlink(target, root.path(path)).unwrap();
+        };
+        link(
+            "/nix/store/0a1b-nixos-system",
+            "/nix/var/nix/profiles/system-7-link",
+        );
+        link("system-7-link", "/nix/var/nix/profiles/system");
+        link(
+            "/nix/store/2c3d-system-path",
+            "/nix/store/0a1b-nixos-system/sw",
+        );
+        link(
+            "/nix/store/4e5f-bash-5.2/bin/bash",
+            "/nix/store/2c3d-system-path/bin/bash",
+        );
+        link(
+            "/nix/store/4e5f-bash-5.2/bin/sh",
+            "/nix/store/2c3d-system-path/bin/sh",
+        );
+        link(
+            "/nix/store/6a7b-git-2.44/bin/git",
+            "/nix/store/2c3d-system-path/bin/git",
+        );
+        let opts = Options::default().with_root(nixos.root());
+        assert_eq!(get_num_packages(&opts), Ok(2));
+
+        // rpm is the only database, that can not be read, which is said.
+        let fedora = Fixture::new().file("/usr/lib/sysimage/rpm/rpmdb.sqlite", "");
+        let opts = Options::default().with_root(fedora.root());
+        assert_eq!(
+            get_num_packages(&opts),
+            Err(FetchError::NotFound(
+                "the rpm database in /usr/lib/sysimage/rpm can only be read by rpm".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn nix_profile_from_home() {
+        // Without a shell, `~` would be passed to `nix-store` literally.
+        let home = std::env::var("HOME").unwrap();
+        let profile = format!("{}/.nix-profile/", home.trim_end_matches('/'));
+        let runner = CannedRunner::new()
+            .with(
+                "nix-store -qR /run/current-system/sw/",
+                0,
+                "/nix/store/0a1b-bash-5.2\n/nix/store/2c3d-zsh-5.9\n",
+            )
+            .with(
+                &format!("nix-store -qR {}", profile),
+                0,
+                "/nix/store/4e5f-ripgrep-14.1\n",
+            );
+        assert_eq!(get_num_packages(&canned(runner)), Ok(3));
+    }
+
+    #[test]
+    fn failed_manager_is_skipped() {
+        // `pacman` is installed, but fails, so the next manager is tried.
+        let runner = CannedRunner::new().with("pacman -Q", 1, "").with(
+            "xbps-query -l",
+            0,
+            "ii bash-5.2\nii zsh-5.9\nii vim-9.0\n",
+        );
+        assert_eq!(get_num_packages(&canned(runner)), Ok(3));
+
+        // The failure of `pacman` is more useful, than the other
+        // managers not being installed.
+        let runner = CannedRunner::new().with("pacman -Q", 1, "");
+        assert!(matches!(
+            get_num_packages(&canned(runner)),
+            Err(FetchError::CommandFailed(_))
+        ));
+        assert!(get_num_packages(&canned(CannedRunner::new()))
+            .unwrap_err()
+            .is_not_found());
     }
 }
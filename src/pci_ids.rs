@@ -0,0 +1,102 @@
+// @Brief : This file contains a parser for the PCI ID database, `pci.ids`,
+//          which names the vendors and devices, `lspci` would show.
+
+use crate::error::ResultExt;
+use crate::{FetchError, Options};
+
+/// Where distributions install `pci.ids`, i.e. the `hwdata` package on
+/// Fedora and Arch, or `pciutils` on Debian.
+pub const PATHS: &[&str] = &["/usr/share/hwdata/pci.ids", "/usr/share/misc/pci.ids"];
+
+/// The name of a vendor, and of one of its devices, from `pci.ids`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PciName {
+    /// e.g. "Intel Corporation".
+    pub vendor: Option<String>,
+    /// e.g. "TigerLake-LP GT2 [Iris Xe Graphics]".
+    pub device: Option<String>,
+}
+
+/// Read the PCI ID database, from the first of `PATHS`, that exists.
+pub fn read(opts: &Options) -> Result<String, FetchError> {
+    crate::read_traced(opts, PATHS[0]).or_try(|| crate::read_traced(opts, PATHS[1]))
+}
+
+/// Look up the `vendor` and `device` IDs in `ids`, the contents of
+/// `pci.ids`. Either of the names is `None`, if the database does not
+/// know about it, e.g. a GPU newer than the database.
+pub fn lookup(ids: &str, vendor: u16, device: u16) -> PciName {
+    // Vendors start at the beginning of the line, e.g. "8086  Intel
+    // Corporation", followed by their devices, indented with one tab,
+    // and the subsystems of these, with two tabs.
+    let mut name = PciName::default();
+    for line in ids.lines() {
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        // The device classes follow the vendors, e.g. "C 03  Display
+        // controller".
+        if line.starts_with("C ") {
+            break;
+        }
+
+        match line.strip_prefix('\t') {
+            None if name.vendor.is_some() => break,
+            None => name.vendor = parse_entry(line, vendor),
+            Some(entry) if name.vendor.is_some() && !entry.starts_with('\t') => {
+                if let Some(device) = parse_entry(entry, device) {
+                    name.device = Some(device);
+                    break;
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    name
+}
+
+/// The name in `entry`, e.g. "10de  NVIDIA Corporation", if its ID is `id`.
+fn parse_entry(entry: &str, id: u16) -> Option<String> {
+    let (entry_id, name) = entry.split_once(char::is_whitespace)?;
+    let entry_id = u16::from_str_radix(entry_id, 16).ok()?;
+    (entry_id == id).then(|| name.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDS: &str = "\
+# List of PCI ID's
+#
+10de  NVIDIA Corporation
+\t25a2  GA107M [GeForce RTX 3050 Mobile]
+\t\t1043 16ed  GeForce RTX 3050 Mobile
+8086  Intel Corporation
+\t1237  440FX - 82441FX PMC [Natoma]
+\t9a49  TigerLake-LP GT2 [Iris Xe Graphics]
+C 03  Display controller
+\t00  VGA compatible controller
+";
+
+    #[test]
+    fn vendors_and_devices() {
+        assert_eq!(
+            lookup(IDS, 0x8086, 0x9a49),
+            PciName {
+                vendor: Some("Intel Corporation".to_string()),
+                device: Some("TigerLake-LP GT2 [Iris Xe Graphics]".to_string()),
+            }
+        );
+        assert_eq!(
+            lookup(IDS, 0x10de, 0x25a2).device.as_deref(),
+            Some("GA107M [GeForce RTX 3050 Mobile]")
+        );
+
+        // Neither a subsystem, nor the device of another vendor match.
+        let unknown = lookup(IDS, 0x10de, 0x1043);
+        assert_eq!(unknown.vendor.as_deref(), Some("NVIDIA Corporation"));
+        assert_eq!(unknown.device, None);
+        assert_eq!(lookup(IDS, 0x1002, 0x73bf), PciName::default());
+    }
+}
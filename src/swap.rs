@@ -0,0 +1,244 @@
+// @Brief : This file contains the swap usage, from `/proc/swaps`, along
+//          with the compression ratio of the zram devices among them.
+
+use crate::error::ResultExt;
+use crate::{FetchError, Options, SizeUnit};
+use std::fmt;
+
+/// The swap areas in use, in the order of `/proc/swaps`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SwapInfo {
+    pub devices: Vec<SwapDevice>,
+}
+
+/// A single swap area, e.g. "/dev/zram0", or "/swapfile".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapDevice {
+    /// Path of the device, or the file.
+    pub name: String,
+    pub kind: SwapKind,
+    /// Size of the area, in bytes.
+    pub size: u64,
+    /// Swap in use on the area, in bytes.
+    pub used: u64,
+    /// The data stored on a zram device, or `None`, when its stats cannot
+    /// be read, or it is not zram at all.
+    pub zram: Option<ZramStats>,
+}
+
+/// The size of the data stored on a zram device, before and after
+/// compression, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZramStats {
+    pub original: u64,
+    pub compressed: u64,
+}
+
+impl ZramStats {
+    /// How many times smaller the data got, e.g. 3.2, or `None`, when the
+    /// device holds no data yet.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.compressed > 0).then(|| self.original as f64 / self.compressed as f64)
+    }
+}
+
+/// What a swap area is backed by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapKind {
+    Partition,
+    File,
+    /// A compressed block device in RAM, which `/proc/swaps` lists as a
+    /// partition.
+    Zram,
+}
+
+impl fmt::Display for SwapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SwapKind::Partition => "partition",
+            SwapKind::File => "file",
+            SwapKind::Zram => "zram",
+        })
+    }
+}
+
+impl SwapInfo {
+    /// Swap in use on all the areas, in bytes.
+    pub fn used(&self) -> u64 {
+        self.devices.iter().map(|device| device.used).sum()
+    }
+
+    /// Size of all the areas, in bytes.
+    pub fn total(&self) -> u64 {
+        self.devices.iter().map(|device| device.size).sum()
+    }
+
+    /// The share of the swap in use, in percent.
+    pub fn percent(&self) -> u64 {
+        (self.used() * 100).checked_div(self.total()).unwrap_or(0)
+    }
+
+    /// Render the usage in `unit`, followed by the percentage, if `percent`
+    /// is set, and the kinds of the areas, e.g. "512 MiB / 8192 MiB (6%,
+    /// zram 3.20x, file)", or "None", without any swap.
+    pub fn display(&self, unit: SizeUnit, percent: bool) -> String {
+        if self.devices.is_empty() {
+            return "None".to_string();
+        }
+
+        let usage = format!(
+            "{} / {}",
+            unit.format(self.used()),
+            unit.format(self.total())
+        );
+        let mut details = Vec::new();
+        if percent {
+            details.push(format!("{}%", self.percent()));
+        }
+
+        // Each kind once, e.g. two swap files are just "file".
+        for kind in [SwapKind::Zram, SwapKind::Partition, SwapKind::File] {
+            let devices = self.devices.iter().filter(|device| device.kind == kind);
+            if devices.clone().next().is_none() {
+                continue;
+            }
+            // The ratio of all the zram devices together, rather than the
+            // one of each.
+            let stats = devices
+                .filter_map(|device| device.zram)
+                .reduce(|total, stats| ZramStats {
+                    original: total.original + stats.original,
+                    compressed: total.compressed + stats.compressed,
+                });
+            match stats.and_then(|stats| stats.ratio()) {
+                Some(ratio) => details.push(format!("{} {:.2}x", kind, ratio)),
+                None => details.push(kind.to_string()),
+            }
+        }
+        format!("{} ({})", usage, details.join(", "))
+    }
+}
+
+impl fmt::Display for SwapInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.display(SizeUnit::default(), false))
+    }
+}
+
+/// Read the swap areas from `/proc/swaps`. A system without swap has no
+/// areas, which is not an error.
+pub fn read(opts: &Options) -> Result<SwapInfo, FetchError> {
+    let swaps = crate::read_traced(opts, "/proc/swaps")?;
+
+    // "Filename  Type  Size  Used  Priority", with the sizes in KiB.
+    let devices = swaps
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = crate::unescape_octal(fields.next()?);
+            let kind = fields.next()?;
+            let size: u64 = fields.next()?.parse().ok()?;
+            let used: u64 = fields.next()?.parse().ok()?;
+
+            let zram = name
+                .strip_prefix("/dev/")
+                .filter(|device| device.starts_with("zram"));
+            let (kind, zram) = match (zram, kind) {
+                (Some(device), _) => (SwapKind::Zram, zram_stats(opts, device)),
+                (None, "file") => (SwapKind::File, None),
+                (None, _) => (SwapKind::Partition, None),
+            };
+            Some(SwapDevice {
+                name,
+                kind,
+                size: size * 1024,
+                used: used * 1024,
+                zram,
+            })
+        })
+        .collect();
+    Ok(SwapInfo { devices })
+}
+
+/// The size of the data stored on the zram `device`, e.g. "zram0".
+fn zram_stats(opts: &Options, device: &str) -> Option<ZramStats> {
+    let (original, compressed) =
+        crate::read_traced(opts, &format!("/sys/block/{}/mm_stat", device))
+            .and_then(|mm_stat| {
+                // "orig_data_size compr_data_size mem_used_total ...", in bytes.
+                let mut fields = mm_stat.split_whitespace().map(str::parse::<u64>);
+                match (fields.next(), fields.next()) {
+                    (Some(Ok(original)), Some(Ok(compressed))) => Ok((original, compressed)),
+                    _ => Err(FetchError::ParseError(format!(
+                        "invalid mm_stat for {}",
+                        device
+                    ))),
+                }
+            })
+            // Kernels older than 4.1 have a file for each of the stats.
+            .or_try(|| {
+                let stat = |file: &str| -> Result<u64, FetchError> {
+                    let path = format!("/sys/block/{}/{}", device, file);
+                    crate::read_traced(opts, &path)?
+                        .trim()
+                        .parse()
+                        .map_err(|_| FetchError::ParseError(format!("invalid value in {}", path)))
+                };
+                Ok((stat("orig_data_size")?, stat("compr_data_size")?))
+            })
+            .ok()?;
+    Some(ZramStats {
+        original,
+        compressed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn detect(fixture: &Fixture) -> SwapInfo {
+        read(&Options::default().with_root(fixture.root())).unwrap()
+    }
+
+    #[test]
+    fn zram_and_files() {
+        let fixture = Fixture::new()
+            .file(
+                "/proc/swaps",
+                "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n\
+                 /dev/zram0                              partition\t4194300\t\t524288\t\t100\n\
+                 /var/lib/swap\\040file                   file\t\t1048576\t\t0\t\t-2\n",
+            )
+            .file(
+                "/sys/block/zram0/mm_stat",
+                "536870912 134217728 140000000        0 140000000        0        0        0\n",
+            );
+        let swap = detect(&fixture);
+        assert_eq!(swap.devices.len(), 2);
+        assert_eq!(swap.devices[0].kind, SwapKind::Zram);
+        assert_eq!(swap.devices[1].name, "/var/lib/swap file");
+        assert_eq!(swap.devices[1].kind, SwapKind::File);
+        assert_eq!(swap.used(), 512 * MIB);
+        assert_eq!(
+            swap.display(SizeUnit::Mib, true),
+            "512 MiB / 5120 MiB (10%, zram 4.00x, file)"
+        );
+
+        let partition = Fixture::new().file(
+            "/proc/swaps",
+            "Filename\tType\tSize\tUsed\tPriority\n/dev/sda2 partition 2097148 0 -2\n",
+        );
+        assert_eq!(
+            detect(&partition).to_string(),
+            "0 MiB / 2048 MiB (partition)"
+        );
+
+        let none = Fixture::new().file("/proc/swaps", "Filename\tType\tSize\tUsed\tPriority\n");
+        assert_eq!(detect(&none).to_string(), "None");
+    }
+}
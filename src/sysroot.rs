@@ -0,0 +1,191 @@
+// @Brief : This file contains `SysRoot`, the directory under which the
+//          probes look for `/etc`, `/proc` and `/sys`.
+
+use crate::FetchError;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The root directory of the system being described.
+///
+/// This is `/` for the running system, but it can point to a mounted disk
+/// image, a chroot or an extracted container filesystem instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SysRoot {
+    root: PathBuf,
+}
+
+impl SysRoot {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        SysRoot { root: root.into() }
+    }
+
+    /// The root of the running system, i.e. `/`.
+    pub fn host() -> Self {
+        SysRoot::new("/")
+    }
+
+    /// Whether this is the root of the running system. Probes that ask
+    /// the running system directly, e.g. through external commands, are
+    /// only meaningful in that case.
+    pub fn is_host(&self) -> bool {
+        self.root == Path::new("/")
+    }
+
+    /// Resolve an absolute system path, e.g. "/etc/os-release", under
+    /// this root.
+    pub fn path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    /// Read the file at the absolute system path `path`, under this root.
+    pub fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String, FetchError> {
+        let path = self.path(path);
+        fs::read_to_string(&path).map_err(|err| FetchError::from_io(&path.to_string_lossy(), err))
+    }
+
+    /// Read the target of the symbolic link at the absolute system path
+    /// `path`, under this root, e.g. "/proc/1/exe".
+    pub fn read_link<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, FetchError> {
+        let path = self.path(path);
+        fs::read_link(&path).map_err(|err| FetchError::from_io(&path.to_string_lossy(), err))
+    }
+
+    /// The names of the entries in the directory at the absolute system
+    /// path `path`, under this root, sorted, e.g. "cpu0", "cpu1".
+    pub fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<String>, FetchError> {
+        let path = self.path(path);
+        let err = |err| FetchError::from_io(&path.to_string_lossy(), err);
+        let mut names = fs::read_dir(&path)
+            .map_err(err)?
+            .map(|entry| {
+                Ok(entry
+                    .map_err(err)?
+                    .file_name()
+                    .to_string_lossy()
+                    .into_owned())
+            })
+            .collect::<Result<Vec<_>, FetchError>>()?;
+        names.sort();
+        Ok(names)
+    }
+
+    /// Resolve the symbolic links in the absolute system path `path`, the
+    /// way the system under this root would, i.e. with its absolute links
+    /// pointing into this root rather than out of it, e.g. the profiles in
+    /// `/nix/var/nix/profiles`. Returns an absolute system path.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, FetchError> {
+        // Linux gives up after 40 links as well.
+        const MAX_LINKS: u32 = 40;
+
+        let err = |path: &Path, err| FetchError::from_io(&self.path(path).to_string_lossy(), err);
+        let mut resolved = PathBuf::from("/");
+        let mut pending: Vec<OsString> = components(path.as_ref());
+        let mut links = 0;
+        while let Some(name) = pending.pop() {
+            if name == ".." {
+                resolved.pop();
+                continue;
+            }
+            let candidate = resolved.join(&name);
+            let meta =
+                fs::symlink_metadata(self.path(&candidate)).map_err(|e| err(&candidate, e))?;
+            if !meta.file_type().is_symlink() {
+                resolved = candidate;
+                continue;
+            }
+
+            links += 1;
+            if links > MAX_LINKS {
+                return Err(FetchError::ParseError(format!(
+                    "too many symbolic links in {}",
+                    self.path(path).to_string_lossy()
+                )));
+            }
+            let target = fs::read_link(self.path(&candidate)).map_err(|e| err(&candidate, e))?;
+            if target.is_absolute() {
+                resolved = PathBuf::from("/");
+            }
+            pending.extend(components(&target));
+        }
+        Ok(resolved)
+    }
+
+    /// Whether anything exists at the absolute system path `path`, under
+    /// this root.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.path(path).exists()
+    }
+}
+
+/// The names in `path`, in reverse, leaving out the root and `.`, so that
+/// the first one can be popped off the end.
+fn components(path: &Path) -> Vec<OsString> {
+    let mut names: Vec<OsString> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_os_string()),
+            Component::ParentDir => Some(OsString::from("..")),
+            _ => None,
+        })
+        .collect();
+    names.reverse();
+    names
+}
+
+impl Default for SysRoot {
+    fn default() -> Self {
+        SysRoot::host()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    #[test]
+    fn resolve_paths() {
+        let root = SysRoot::new("/mnt/image");
+        assert!(!root.is_host());
+        assert_eq!(
+            root.path("/etc/os-release"),
+            Path::new("/mnt/image/etc/os-release")
+        );
+        assert_eq!(
+            SysRoot::host().path("/proc/uptime"),
+            Path::new("/proc/uptime")
+        );
+        assert!(SysRoot::default().is_host());
+    }
+
+    #[test]
+    fn resolve_links_inside_root() {
+        let fixture = Fixture::new().dir("/nix/store/0a1b-nixos-system/sw-dir/bin");
+        let root = fixture.root();
+        let link = |target: &str, path: &str| {
+            std::os::unix::fs::symlink(target, root.path(path)).unwrap();
+        };
+        link("/nix/store/0a1b-nixos-system", "/nix/system-1-link");
+        link("system-1-link", "/nix/system");
+        link("sw-dir", "/nix/store/0a1b-nixos-system/sw");
+
+        assert_eq!(
+            root.resolve("/nix/system/sw/bin"),
+            Ok(PathBuf::from("/nix/store/0a1b-nixos-system/sw-dir/bin"))
+        );
+        // `..` leaves the target of the link, not the link itself.
+        assert_eq!(
+            root.resolve("/nix/system/../0a1b-nixos-system/sw"),
+            Ok(PathBuf::from("/nix/store/0a1b-nixos-system/sw-dir"))
+        );
+        assert!(root.resolve("/nix/missing").unwrap_err().is_not_found());
+
+        link("/nix/loop", "/nix/loop");
+        assert!(matches!(
+            root.resolve("/nix/loop"),
+            Err(FetchError::ParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+// @Brief : This file contains helpers shared by the tests, for building
+//          fake system roots on disk.
+
+use crate::SysRoot;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A temporary directory, laid out like a system root, which is
+/// removed once it goes out of scope.
+pub struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "fetchit-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        Fixture { dir }
+    }
+
+    /// Create the file at the absolute system path `path`, with `contents`.
+    pub fn file(self, path: &str, contents: &str) -> Self {
+        let path = self.root().path(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        self
+    }
+
+    /// Create the directory at the absolute system path `path`.
+    pub fn dir(self, path: &str) -> Self {
+        fs::create_dir_all(self.root().path(path)).unwrap();
+        self
+    }
+
+    pub fn root(&self) -> SysRoot {
+        SysRoot::new(&self.dir)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
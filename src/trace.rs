@@ -0,0 +1,94 @@
+// @Brief : This file contains `Trace`, which records every source a probe
+//          tried in its chain of fallbacks, for the `--doctor` mode.
+
+use crate::FetchError;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A single source, that a probe tried, e.g. "/etc/os-release".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    /// What was tried, e.g. a path, a command line, or an env variable.
+    pub source: String,
+    /// A short rendering of the value, that the source produced, or
+    /// why it failed.
+    pub outcome: Result<String, FetchError>,
+    /// How long the source took.
+    pub elapsed: Duration,
+}
+
+/// The attempts of the probe for a single field, in the order they were
+/// made. Clones share the same attempts.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    attempts: Arc<Mutex<Vec<Attempt>>>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace::default()
+    }
+
+    /// Run `source_fn`, and record its outcome as an attempt of `source`.
+    pub fn attempt<T, F>(&self, source: &str, source_fn: F) -> Result<T, FetchError>
+    where
+        T: fmt::Debug,
+        F: FnOnce() -> Result<T, FetchError>,
+    {
+        let start = Instant::now();
+        let result = source_fn();
+        let elapsed = start.elapsed();
+
+        let outcome = match &result {
+            Ok(value) => Ok(summarize(value)),
+            Err(err) => Err(err.clone()),
+        };
+        self.lock().push(Attempt {
+            source: source.to_string(),
+            outcome,
+            elapsed,
+        });
+
+        result
+    }
+
+    /// Every attempt recorded so far.
+    pub fn attempts(&self) -> Vec<Attempt> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Attempt>> {
+        self.attempts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Render `value` on a single line, cut short after 60 characters, e.g.
+/// for the complete list of packages.
+fn summarize<T: fmt::Debug>(value: &T) -> String {
+    let summary = format!("{:?}", value);
+    match summary.char_indices().nth(60) {
+        Some((end, _)) => format!("{}...", &summary[..end]),
+        None => summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_attempts_in_order() {
+        let trace = Trace::new();
+        let missing = FetchError::NotFound("/etc/os-release".to_string());
+
+        let _ = trace.attempt("/etc/os-release", || Err::<String, _>(missing.clone()));
+        let _ = trace.attempt("packages", || Ok("x".repeat(100)));
+
+        let attempts = trace.clone().attempts();
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].outcome, Err(missing));
+        assert_eq!(attempts[1].source, "packages");
+        assert!(attempts[1].outcome.as_ref().unwrap().ends_with("..."));
+    }
+}
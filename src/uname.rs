@@ -0,0 +1,47 @@
+// @Brief : This file contains a safe wrapper around `uname(2)`, which
+//          describes the running kernel without spawning `uname`.
+
+use std::ffi::CStr;
+use std::mem::MaybeUninit;
+
+/// The fields reported by `uname(2)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Uname {
+    /// Network name of the machine, e.g. "archlinux".
+    pub nodename: String,
+    /// Kernel release, e.g. "6.1.0-13-amd64".
+    pub release: String,
+    /// Kernel build string, e.g. "#1 SMP PREEMPT_DYNAMIC Debian 6.1.55-1".
+    pub version: String,
+    /// Hardware architecture, e.g. "x86_64".
+    pub machine: String,
+}
+
+/// Call `uname(2)` for the running kernel.
+pub(crate) fn uname() -> Option<Uname> {
+    let mut buf = MaybeUninit::<libc::utsname>::zeroed();
+
+    // SAFETY: `buf` is a valid, writable `utsname`, and on success the
+    // kernel fills every field with a NUL terminated string.
+    let buf = unsafe {
+        if libc::uname(buf.as_mut_ptr()) != 0 {
+            return None;
+        }
+        buf.assume_init()
+    };
+
+    // Convert one of the NUL terminated fields into a `String`.
+    let field = |chars: &[libc::c_char]| {
+        // SAFETY: the field lies within `buf`, and is NUL terminated.
+        unsafe { CStr::from_ptr(chars.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    Some(Uname {
+        nodename: field(&buf.nodename),
+        release: field(&buf.release),
+        version: field(&buf.version),
+        machine: field(&buf.machine),
+    })
+}
@@ -0,0 +1,258 @@
+// @Brief : This file contains the detection of the hypervisor, and the
+//          container, `fetchit` is running in, if any.
+
+use crate::{FetchError, Options};
+use std::fmt;
+
+/// Where the system runs, e.g. in Docker on a KVM guest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Virtualization {
+    /// The hypervisor of the virtual machine, if the system runs in one.
+    pub hypervisor: Option<Hypervisor>,
+    /// The container runtime, e.g. "Docker", or "LXC".
+    pub container: Option<String>,
+}
+
+/// The hypervisor of a virtual machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hypervisor {
+    /// A hypervisor, that identifies itself, e.g. "KVM", or "WSL2".
+    Named(String),
+    /// The CPU reports, that it is virtualized, but the hypervisor does
+    /// not tell its name.
+    Unknown,
+}
+
+impl fmt::Display for Virtualization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.container, &self.hypervisor) {
+            (Some(container), Some(Hypervisor::Named(name))) => {
+                write!(f, "{} on {}", container, name)
+            }
+            (Some(container), Some(Hypervisor::Unknown)) => {
+                write!(f, "{} in a virtual machine", container)
+            }
+            (None, Some(Hypervisor::Named(name))) | (Some(name), None) => f.write_str(name),
+            (None, Some(Hypervisor::Unknown)) => f.write_str("Virtual machine"),
+            (None, None) => f.write_str("None"),
+        }
+    }
+}
+
+/// Well known DMI vendors and product names of virtual machines, and
+/// the name of their hypervisor.
+const DMI_HYPERVISORS: &[(&str, &str)] = &[
+    ("KVM", "KVM"),
+    ("QEMU", "QEMU"),
+    ("VirtualBox", "VirtualBox"),
+    ("innotek GmbH", "VirtualBox"),
+    ("VMware", "VMware"),
+    ("Microsoft Corporation Virtual Machine", "Hyper-V"),
+    ("Xen", "Xen"),
+    ("Amazon EC2", "Amazon EC2"),
+    ("Google Compute Engine", "Google Compute Engine"),
+    ("Parallels", "Parallels"),
+    ("BHYVE", "bhyve"),
+    ("Bochs", "Bochs"),
+];
+
+/// Detect the hypervisor and the container. Both of them are `None`,
+/// when the system runs on bare metal.
+pub fn read(opts: &Options) -> Result<Virtualization, FetchError> {
+    Ok(Virtualization {
+        hypervisor: hypervisor(opts),
+        container: container(opts),
+    })
+}
+
+fn hypervisor(opts: &Options) -> Option<Hypervisor> {
+    let read = |path: &str| crate::read_traced(opts, path);
+
+    // WSL runs a Microsoft kernel, without any DMI info.
+    if let Ok(release) = read("/proc/sys/kernel/osrelease") {
+        if release.contains("WSL2") || release.contains("microsoft") {
+            return Some(named("WSL2"));
+        }
+        if release.contains("Microsoft") {
+            return Some(named("WSL"));
+        }
+    }
+
+    // The vendor and product of the machine name the hypervisor, e.g.
+    // "QEMU Standard PC (Q35 + ICH9, 2009)".
+    let dmi: Vec<String> = ["sys_vendor", "product_name", "bios_vendor"]
+        .iter()
+        .filter_map(|file| read(&format!("/sys/class/dmi/id/{}", file)).ok())
+        .map(|value| value.trim().to_string())
+        .collect();
+    let dmi = dmi.join(" ");
+    if let Some(&(_, name)) = DMI_HYPERVISORS
+        .iter()
+        .find(|(pattern, _)| dmi.contains(pattern))
+    {
+        // QEMU reports itself as the vendor, whether it emulates the CPU,
+        // or runs the guest on KVM, as it usually does.
+        if name == "QEMU" && kvm_guest(opts) {
+            return Some(named("KVM"));
+        }
+        return Some(named(name));
+    }
+
+    // Xen guests without DMI, e.g. paravirtualized ones.
+    if let Ok(hypervisor_type) = read("/sys/hypervisor/type") {
+        match hypervisor_type.trim() {
+            "" => {}
+            "xen" => return Some(named("Xen")),
+            other => return Some(named(other)),
+        }
+    }
+
+    // The CPU knows it is virtualized, even when the hypervisor hides.
+    let cpuinfo = read("/proc/cpuinfo").ok()?;
+    cpuinfo
+        .lines()
+        .filter(|line| line.starts_with("flags"))
+        .any(|line| line.split_whitespace().any(|flag| flag == "hypervisor"))
+        .then_some(Hypervisor::Unknown)
+}
+
+/// Whether the guest runs on KVM, which provides the paravirtualized
+/// `kvm-clock`, and names its default CPU model "Common KVM processor".
+fn kvm_guest(opts: &Options) -> bool {
+    let clocksources = crate::read_traced(
+        opts,
+        "/sys/devices/system/clocksource/clocksource0/available_clocksource",
+    )
+    .unwrap_or_default();
+    if clocksources
+        .split_whitespace()
+        .any(|clock| clock == "kvm-clock")
+    {
+        return true;
+    }
+    crate::read_traced(opts, "/proc/cpuinfo")
+        .unwrap_or_default()
+        .lines()
+        .any(|line| line.starts_with("model name") && line.contains("KVM"))
+}
+
+fn named(name: &str) -> Hypervisor {
+    Hypervisor::Named(name.to_string())
+}
+
+fn container(opts: &Options) -> Option<String> {
+    let root = opts.root();
+    let marker = |path: &str| {
+        opts.attempt(path, || {
+            if root.exists(path) {
+                Ok(())
+            } else {
+                Err(FetchError::NotFound(format!("{} does not exist", path)))
+            }
+        })
+        .is_ok()
+    };
+
+    if marker("/.dockerenv") {
+        return Some("Docker".to_string());
+    }
+    if marker("/run/.containerenv") {
+        return Some("Podman".to_string());
+    }
+
+    // Set by most runtimes for PID 1, e.g. "container=lxc". Reading it
+    // usually needs root, though.
+    if let Ok(environ) = crate::read_traced(opts, "/proc/1/environ") {
+        if let Some(name) = environ
+            .split('\0')
+            .find_map(|var| var.strip_prefix("container="))
+            .filter(|name| !name.is_empty())
+        {
+            return Some(container_name(name));
+        }
+    }
+
+    // The cgroup of PID 1 is named after the runtime, with cgroup v1.
+    let cgroup = crate::read_traced(opts, "/proc/1/cgroup").ok()?;
+    [
+        ("/docker", "Docker"),
+        ("/libpod", "Podman"),
+        ("/lxc", "LXC"),
+        ("kubepods", "Kubernetes"),
+    ]
+    .iter()
+    .find(|(pattern, _)| cgroup.contains(pattern))
+    .map(|(_, name)| name.to_string())
+}
+
+/// The name of the runtime, from the value of `container=`.
+fn container_name(name: &str) -> String {
+    match name {
+        "docker" => "Docker",
+        "podman" => "Podman",
+        "lxc" | "lxc-libvirt" => "LXC",
+        "oci" => "OCI",
+        other => other,
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::Fixture;
+
+    fn detect(fixture: &Fixture) -> Virtualization {
+        read(&Options::default().with_root(fixture.root())).unwrap()
+    }
+
+    #[test]
+    fn containers_and_hypervisors() {
+        let docker_on_kvm = Fixture::new()
+            .file("/.dockerenv", "")
+            .file("/sys/class/dmi/id/sys_vendor", "QEMU\n")
+            .file(
+                "/sys/class/dmi/id/product_name",
+                "Standard PC (Q35 + ICH9, 2009)\n",
+            )
+            .file(
+                "/sys/devices/system/clocksource/clocksource0/available_clocksource",
+                "kvm-clock tsc hpet acpi_pm \n",
+            );
+        assert_eq!(detect(&docker_on_kvm).to_string(), "Docker on KVM");
+
+        // QEMU emulating the CPU itself, without KVM.
+        let qemu = Fixture::new()
+            .file("/sys/class/dmi/id/sys_vendor", "QEMU\n")
+            .file(
+                "/proc/cpuinfo",
+                "processor\t: 0\nmodel name\t: QEMU Virtual CPU version 2.5+\n",
+            );
+        assert_eq!(detect(&qemu).to_string(), "QEMU");
+        let kvm64 = qemu.file(
+            "/proc/cpuinfo",
+            "processor\t: 0\nmodel name\t: Common KVM processor\n",
+        );
+        assert_eq!(detect(&kvm64).to_string(), "KVM");
+
+        let lxc = Fixture::new()
+            .file("/proc/1/environ", "PATH=/bin\0container=lxc\0")
+            .file(
+                "/proc/cpuinfo",
+                "processor\t: 0\nflags\t\t: fpu hypervisor sse\n",
+            );
+        assert_eq!(detect(&lxc).hypervisor, Some(Hypervisor::Unknown));
+        assert_eq!(detect(&lxc).to_string(), "LXC in a virtual machine");
+
+        let wsl = Fixture::new().file(
+            "/proc/sys/kernel/osrelease",
+            "5.15.90.1-microsoft-standard-WSL2\n",
+        );
+        assert_eq!(detect(&wsl).hypervisor, Some(named("WSL2")));
+
+        let bare_metal = Fixture::new()
+            .file("/proc/1/cgroup", "0::/init.scope\n")
+            .file("/proc/cpuinfo", "flags\t\t: fpu sse\n");
+        assert_eq!(detect(&bare_metal), Virtualization::default());
+    }
+}